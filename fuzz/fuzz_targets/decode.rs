@@ -0,0 +1,17 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use huff::compress::{decode, encode};
+use huff::format::read_frame;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(output) = decode(data) {
+        // Unframed files predate the other methods and spend at least a bit on every byte.
+        match read_frame(data).ok().and_then(|frame| frame.trailer) {
+            Some(trailer) => assert_eq!(trailer.length, output.len()),
+            None => assert!(output.len() <= data.len() * 8),
+        }
+    }
+
+    assert_eq!(decode(&encode(data)).as_deref(), Ok(data));
+});
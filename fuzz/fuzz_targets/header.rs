@@ -0,0 +1,20 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use huff::format::{read_code_lengths, read_dictionary, read_sizes};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((dictionary_size, size_when_compressed, remaining)) = read_sizes(data) {
+        assert!(dictionary_size <= remaining.len());
+        assert!(size_when_compressed <= (remaining.len() - dictionary_size) * 8);
+    }
+
+    if let Ok((tokens, hits)) = read_dictionary(data) {
+        assert_eq!(tokens.len(), hits.len());
+        assert!(hits.iter().sum::<usize>() <= data.len() * 8);
+    }
+
+    if let Ok(lengths) = read_code_lengths(data) {
+        assert_eq!(256, lengths.len());
+    }
+});
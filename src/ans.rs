@@ -0,0 +1,349 @@
+//! Table-based asymmetric numeral systems (tANS), the coding behind FSE. Counts are normalised to
+//! a table of `1 << table_log` states. Every state decodes to one symbol and says how many bits
+//! to read for the next state, so a symbol costs close to the fraction of a bit its probability
+//! calls for instead of the whole bits of a huffman code.
+
+use crate::bytes::{
+    bytes_from, push_bits, read_be_u8, read_varint, usize_to_varint, BitReader, Codes,
+};
+use crate::error::{Error, Result};
+use crate::histogram::{self, Histogram};
+
+/// The largest table, and the one used for all but small inputs.
+pub const TABLE_LOG: u32 = 11;
+/// Tables this small still hold a state for up to 32 distinct bytes.
+const MIN_TABLE_LOG: u32 = 5;
+/// States must fit the 16 bits of a decoding table entry.
+const MAX_TABLE_LOG: u32 = 15;
+
+/// How many states of the table each byte value has.
+pub type Counts = [u32; 256];
+
+/// The table size for an input of this length: no more states than bytes, within the bounds.
+pub fn table_log(length: usize) -> u32 {
+    let bits = usize::BITS - length.saturating_sub(1).leading_zeros();
+    bits.clamp(MIN_TABLE_LOG, TABLE_LOG)
+}
+
+/// Scales the counts to sum to `1 << table_log`, keeping every byte that occurs. The table must
+/// have a state for each of them.
+pub fn normalise(histogram: &Histogram, table_log: u32) -> Counts {
+    let total = histogram.iter().sum::<u64>();
+    let size = 1u64 << table_log;
+    let mut counts = [0; 256];
+    if total == 0 {
+        return counts;
+    }
+
+    for (count, hits) in counts.iter_mut().zip(histogram) {
+        if *hits > 0 {
+            *count = ((*hits as u128 * size as u128 / total as u128) as u32).max(1);
+        }
+    }
+
+    // Rounding leaves states over or missing. Move them one at a time to or from the byte
+    // where that changes the coded size the least.
+    let bits = |s: usize, count: u32| -(histogram[s] as f64) * (count as f64).log2();
+    let mut sum = counts.iter().map(|count| *count as u64).sum::<u64>();
+    while sum > size {
+        let loss = |s: usize| bits(s, counts[s] - 1) - bits(s, counts[s]);
+        let s = (0..256)
+            .filter(|s| counts[*s] > 1)
+            .min_by(|a, b| loss(*a).total_cmp(&loss(*b)))
+            .expect("every byte that occurs fits the table");
+        counts[s] -= 1;
+        sum -= 1;
+    }
+    while sum < size {
+        let gain = |s: usize| bits(s, counts[s]) - bits(s, counts[s] + 1);
+        let s = (0..256)
+            .filter(|s| counts[*s] > 0)
+            .max_by(|a, b| gain(*a).total_cmp(&gain(*b)))
+            .expect("some byte occurs");
+        counts[s] += 1;
+        sum += 1;
+    }
+
+    counts
+}
+
+/// The symbol of every state. Each byte's states are spread across the table with an odd step,
+/// which visits every state of a power of two sized table once.
+fn spread(counts: &Counts, table_log: u32) -> Vec<u8> {
+    let size = 1 << table_log;
+    let step = (size >> 1) + (size >> 3) + 3;
+    let mut table = vec![0; size];
+    let mut position = 0;
+
+    for (s, count) in counts.iter().enumerate() {
+        for _ in 0..*count {
+            table[position] = s as u8;
+            position = (position + step) & (size - 1);
+        }
+    }
+
+    table
+}
+
+/// What a state decodes to: its symbol, and the next state is `base` plus `bits` read bits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Entry {
+    symbol: u8,
+    bits: u8,
+    base: u16,
+}
+
+fn decoding_table(counts: &Counts, table_log: u32) -> Vec<Entry> {
+    let size = 1 << table_log;
+    let mut next = *counts;
+
+    spread(counts, table_log)
+        .into_iter()
+        .map(|symbol| {
+            // The states of a symbol are numbered count..2 * count in table order.
+            let x = next[symbol as usize];
+            next[symbol as usize] += 1;
+            let bits = table_log - x.ilog2();
+            Entry {
+                symbol,
+                bits: bits as u8,
+                base: ((x << bits) - size) as u16,
+            }
+        })
+        .collect()
+}
+
+/// The encoder's view of the table: the states of each symbol, in table order.
+pub struct Tables {
+    table_log: u32,
+    counts: Counts,
+    /// Where the states of each symbol start in `states`.
+    starts: [usize; 256],
+    states: Vec<u16>,
+}
+
+impl Tables {
+    pub fn new(counts: Counts, table_log: u32) -> Self {
+        let mut starts = [0; 256];
+        let mut start = 0;
+        for (s, count) in counts.iter().enumerate() {
+            starts[s] = start;
+            start += *count as usize;
+        }
+
+        let mut states = vec![0; start];
+        let mut next = starts;
+        for (state, symbol) in spread(&counts, table_log).into_iter().enumerate() {
+            states[next[symbol as usize]] = state as u16;
+            next[symbol as usize] += 1;
+        }
+
+        Self {
+            table_log,
+            counts,
+            starts,
+            states,
+        }
+    }
+
+    /// Codes the source back to front, so the decoder reads it front to back. Returns the
+    /// state decoding starts in and the bits it reads, in reading order.
+    fn encode(&self, source: &[u8]) -> (usize, Codes) {
+        let size = 1 << self.table_log;
+        let mut chunks = Vec::with_capacity(source.len());
+        let mut x: u32 = size;
+
+        for t in source.iter().rev() {
+            let count = self.counts[*t as usize];
+            // Shed bits until the state is one of the count..2 * count the symbol has.
+            let most = self.table_log - count.ilog2();
+            let bits = if x >= count << most { most } else { most - 1 };
+            chunks.push((x & ((1 << bits) - 1), bits));
+            x >>= bits;
+
+            let state = self.states[self.starts[*t as usize] + (x - count) as usize];
+            x = size + state as u32;
+        }
+
+        let mut payload = Codes::with_capacity(source.len() * self.table_log as usize);
+        for (value, bits) in chunks.into_iter().rev() {
+            push_bits(&mut payload, value as usize, bits as usize);
+        }
+
+        ((x - size) as usize, payload)
+    }
+}
+
+/// The length of the source, the table log, the normalised count of each byte that occurs, the
+/// state decoding starts in and the bits it reads. None for a source of a single byte value,
+/// whose states would all read zero bits.
+pub fn encode(source: &[u8]) -> Option<Vec<u8>> {
+    let table_log = table_log(source.len());
+    let counts = normalise(&histogram::count(source), table_log);
+    if counts.contains(&(1 << table_log)) {
+        return None;
+    }
+
+    let (state, payload) = if source.is_empty() {
+        (0, Codes::new())
+    } else {
+        Tables::new(counts, table_log).encode(source)
+    };
+
+    let present: Vec<(usize, u32)> = counts
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    let mut header = usize_to_varint(source.len());
+    header.push(table_log as u8);
+    header.extend(usize_to_varint(present.len()));
+    for (s, count) in present {
+        header.push(s as u8);
+        header.extend(usize_to_varint(count as usize));
+    }
+    header.extend(usize_to_varint(state));
+
+    Some([header, bytes_from(payload)].concat())
+}
+
+pub fn decode(body: &[u8]) -> Result<Vec<u8>> {
+    let (length, remaining) = read_varint(body).ok_or(Error::Truncated)?;
+    let (table_log, remaining) = read_be_u8(remaining).ok_or(Error::Truncated)?;
+    let (present, mut remaining) = read_varint(remaining).ok_or(Error::Truncated)?;
+    if length == 0 {
+        return Ok(Vec::new());
+    }
+
+    let table_log = table_log as u32;
+    if !(MIN_TABLE_LOG..=MAX_TABLE_LOG).contains(&table_log) || present > 256 {
+        return Err(Error::InvalidDictionary);
+    }
+
+    let mut counts = [0; 256];
+    let mut previous = None;
+    for _ in 0..present {
+        let (s, rest) = read_be_u8(remaining).ok_or(Error::Truncated)?;
+        let (count, rest) = read_varint(rest).ok_or(Error::Truncated)?;
+        if previous >= Some(s) || count == 0 || count >= 1 << table_log {
+            return Err(Error::InvalidDictionary);
+        }
+        counts[s] = count as u32;
+        previous = Some(s);
+        remaining = rest;
+    }
+    if counts.iter().map(|count| *count as usize).sum::<usize>() != 1 << table_log {
+        return Err(Error::InvalidDictionary);
+    }
+
+    let (mut state, remaining) = read_varint(remaining).ok_or(Error::Truncated)?;
+    if state >= 1 << table_log {
+        return Err(Error::InvalidDictionary);
+    }
+
+    // With two symbols or more, a state that reads no bits moves to a lower state, so at most
+    // a table of symbols is read between bits.
+    if length > (remaining.len() * 8 + 1) << table_log {
+        return Err(Error::Truncated);
+    }
+
+    let table = decoding_table(&counts, table_log);
+    let mut reader = BitReader::new(remaining);
+    let mut output = Vec::with_capacity(length.min(remaining.len() * 8 + 1));
+    for _ in 0..length {
+        let entry = table[state];
+        output.push(entry.symbol);
+        let bits = reader
+            .read_bits(entry.bits as usize)
+            .ok_or(Error::Truncated)?;
+        state = entry.base as usize + bits;
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let skewed: Vec<u8> = (0..50_000u32).map(|i| (i % 97 / 40) as u8).collect();
+        let all: Vec<u8> = (0..=255).collect();
+        let inputs: [&[u8]; 5] = [b"", b"ab", b"abracadabra", &skewed, &all];
+
+        for source in inputs {
+            assert_eq!(source, &decode(&encode(source).unwrap()).unwrap()[..]);
+        }
+        assert_eq!(None, encode(b"aaaa"));
+    }
+
+    #[test]
+    fn normalised_counts_fill_the_table() {
+        let mut histogram = [0; 256];
+        histogram[b'a' as usize] = 1_000_000;
+        histogram[b'b' as usize] = 3;
+        (b'c'..=b'z').for_each(|t| histogram[t as usize] = 1);
+
+        let counts = normalise(&histogram, 5);
+        assert_eq!(32, counts.iter().sum::<u32>());
+        assert!((b'a'..=b'z').all(|t| counts[t as usize] >= 1));
+        assert_eq!(0, counts[0]);
+    }
+
+    #[test]
+    fn beats_whole_bits_on_skewed_input() {
+        // Nine in ten bytes are the same. That is 0.67 bits a byte of information, huffman
+        // spends 1.2 on it.
+        let source: Vec<u8> = (0..100_000u32)
+            .map(|i| if i % 10 == 0 { (i / 10 % 4) as u8 } else { 9 })
+            .collect();
+
+        let encoded = encode(&source).unwrap();
+        assert!(
+            encoded.len() * 8 < source.len() * 7 / 10,
+            "{}",
+            encoded.len()
+        );
+        assert_eq!(source, decode(&encoded).unwrap());
+    }
+
+    #[test]
+    fn corrupt_tables_are_rejected() {
+        let encoded = encode(b"abracadabra").unwrap();
+        // The count of 'a', after the length, table log, number of bytes and the byte itself.
+        let mut bad = encoded.clone();
+        bad[4] += 1;
+        assert_eq!(Err(Error::InvalidDictionary), decode(&bad));
+
+        for len in 0..encoded.len() {
+            assert!(decode(&encoded[..len]).is_err(), "{}", len);
+        }
+    }
+
+    #[test]
+    fn single_symbol_tables_are_rejected() {
+        // 'a' owns the whole table, so every state would read zero bits.
+        let body = [&usize_to_varint(1 << 33)[..], &[5, 1, b'a', 32, 0]].concat();
+        let frame = [
+            crate::format::write_head(crate::format::Method::Ans),
+            body,
+            crate::format::write_trailer(0, 1 << 33),
+        ]
+        .concat();
+
+        assert_eq!(
+            Err(Error::InvalidDictionary),
+            crate::compress::decode(&frame)
+        );
+    }
+
+    #[test]
+    fn lengths_beyond_the_payload_are_rejected() {
+        let encoded = encode(b"abracadabra").unwrap();
+        let forged = [&usize_to_varint(1 << 33)[..], &encoded[1..]].concat();
+
+        assert_eq!(Err(Error::Truncated), decode(&forged));
+    }
+}
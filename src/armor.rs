@@ -0,0 +1,298 @@
+//! Compressed files as text, to paste where binary does not survive. The encoded bytes sit
+//! between begin and end markers naming the encoding, wrapped at `WIDTH` columns, followed by a
+//! line with their CRC-32 in hex:
+//!
+//! ```text
+//! -----BEGIN HUFF BASE64-----
+//! SFVGAgFoZWxsbywgaGVsbG8KHyYl2wAAAAAAAAAN
+//! =9e92dfb5
+//! -----END HUFF BASE64-----
+//! ```
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+
+pub const ENCODINGS: &[&str] = &["base64", "base85"];
+
+/// Lines of encoded text are at most this long.
+const WIDTH: usize = 76;
+const BEGIN: &str = "-----BEGIN HUFF ";
+const END: &str = "-----END HUFF ";
+const DASHES: &str = "-----";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Encoding {
+    /// RFC 4648 base64 with padding.
+    #[default]
+    Base64,
+    /// Base85 with the Z85 alphabet, which has no quotes or backslashes to escape in JSON. A
+    /// last group of fewer than four bytes is written as one character more than it has bytes,
+    /// as in Ascii85.
+    Base85,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(Self::Base64),
+            "base85" => Ok(Self::Base85),
+            _ => Err(format!("unknown encoding: {}", s)),
+        }
+    }
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Base64 => "base64",
+            Self::Base85 => "base85",
+        }
+    }
+
+    /// The name in the markers.
+    fn label(self) -> &'static str {
+        match self {
+            Self::Base64 => "BASE64",
+            Self::Base85 => "BASE85",
+        }
+    }
+
+    fn encode(self, data: &[u8]) -> String {
+        match self {
+            Self::Base64 => base64_encode(data),
+            Self::Base85 => base85_encode(data),
+        }
+    }
+
+    fn decode(self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            Self::Base64 => base64_decode(text),
+            Self::Base85 => base85_decode(text),
+        }
+    }
+}
+
+pub fn armor(data: &[u8], encoding: Encoding) -> String {
+    let encoded = encoding.encode(data);
+
+    let mut text = format!("{}{}{}\n", BEGIN, encoding.label(), DASHES);
+    for line in encoded.as_bytes().chunks(WIDTH) {
+        // Both alphabets are ASCII.
+        text += std::str::from_utf8(line).unwrap_or_default();
+        text.push('\n');
+    }
+    text += &format!("={:08x}\n", crc32fast::hash(data));
+    text += &format!("{}{}{}\n", END, encoding.label(), DASHES);
+
+    text
+}
+
+/// Whether the input starts with an armor begin marker, after any leading whitespace.
+pub fn is_armored(source: &[u8]) -> bool {
+    source.trim_ascii_start().starts_with(BEGIN.as_bytes())
+}
+
+/// The bytes inside the armor, checked against its checksum line. Only whitespace may come
+/// before the begin marker; text after the end marker is ignored.
+pub fn dearmor(source: &[u8]) -> Result<Vec<u8>> {
+    let text = std::str::from_utf8(source).map_err(|_| Error::InvalidArmor)?;
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty());
+
+    let label = lines
+        .next()
+        .and_then(|line| line.strip_prefix(BEGIN))
+        .and_then(|line| line.strip_suffix(DASHES))
+        .ok_or(Error::InvalidArmor)?;
+    let encoding = [Encoding::Base64, Encoding::Base85]
+        .iter()
+        .copied()
+        .find(|encoding| encoding.label() == label)
+        .ok_or(Error::InvalidArmor)?;
+
+    // The checksum and end marker close the armor, everything before them is encoded data.
+    let rest: Vec<&str> = lines.collect();
+    let end = format!("{}{}{}", END, label, DASHES);
+    let end_at = rest
+        .iter()
+        .position(|line| *line == end)
+        .ok_or(Error::Truncated)?;
+    let (checksum, encoded) = rest[..end_at].split_last().ok_or(Error::InvalidArmor)?;
+    let checksum = checksum
+        .strip_prefix('=')
+        .filter(|hex| hex.len() == 8)
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .ok_or(Error::InvalidArmor)?;
+    let encoded = encoded.concat();
+
+    let data = encoding.decode(&encoded).ok_or(Error::InvalidArmor)?;
+    let actual = crc32fast::hash(&data);
+    if checksum != actual {
+        return Err(Error::ChecksumMismatch {
+            expected: checksum,
+            actual,
+        });
+    }
+
+    Ok(data)
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut text = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let bytes = text.as_bytes();
+    if !bytes.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut data = Vec::with_capacity(bytes.len() / 4 * 3);
+    for (n, chunk) in bytes.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && n + 1 != bytes.len() / 4) {
+            return None;
+        }
+
+        let mut group = 0u32;
+        for (i, c) in chunk[..4 - padding].iter().enumerate() {
+            let value = BASE64.iter().position(|b| b == c)? as u32;
+            group |= value << (18 - 6 * i);
+        }
+        data.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+    }
+    Some(data)
+}
+
+const Z85: &[u8; 85] =
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
+
+fn base85_encode(data: &[u8]) -> String {
+    let mut text = String::with_capacity(data.len().div_ceil(4) * 5);
+    for chunk in data.chunks(4) {
+        let mut group = [0; 4];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(group);
+
+        let mut digits = [0; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = Z85[(value % 85) as usize];
+            value /= 85;
+        }
+        digits[..chunk.len() + 1]
+            .iter()
+            .for_each(|digit| text.push(*digit as char));
+    }
+    text
+}
+
+fn base85_decode(text: &str) -> Option<Vec<u8>> {
+    let bytes = text.as_bytes();
+    if bytes.len() % 5 == 1 {
+        return None;
+    }
+
+    let mut data = Vec::with_capacity(bytes.len() / 5 * 4);
+    for chunk in bytes.chunks(5) {
+        // A short group is padded with the highest digit, which rounds its bytes back up.
+        let mut value = 0u64;
+        for i in 0..5 {
+            let digit = match chunk.get(i) {
+                Some(c) => Z85.iter().position(|z| z == c)?,
+                None => 84,
+            };
+            value = value * 85 + digit as u64;
+        }
+        let value = u32::try_from(value).ok()?;
+        data.extend_from_slice(&value.to_be_bytes()[..chunk.len() - 1]);
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn base64_vectors() {
+        // RFC 4648, section 10.
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (data, text) in vectors {
+            assert_eq!(text, base64_encode(data.as_bytes()));
+            assert_eq!(Some(data.as_bytes().to_vec()), base64_decode(text));
+        }
+        assert_eq!(None, base64_decode("Zg==Zg=="));
+        assert_eq!(None, base64_decode("Zm9"));
+    }
+
+    #[test]
+    fn z85_vector() {
+        // The Z85 specification's example.
+        let data = [0x86, 0x4f, 0xd2, 0x6f, 0xb5, 0x59, 0xf7, 0x5b];
+        assert_eq!("HelloWorld", base85_encode(&data));
+        assert_eq!(Some(data.to_vec()), base85_decode("HelloWorld"));
+
+        for len in 0..9 {
+            let data: Vec<u8> = (0..len).map(|i| 255 - i as u8).collect();
+            assert_eq!(Some(data.clone()), base85_decode(&base85_encode(&data)));
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+
+        for encoding in [Encoding::Base64, Encoding::Base85] {
+            let text = armor(&data, encoding);
+            assert!(text.lines().all(|line| line.len() <= WIDTH));
+            assert!(is_armored(text.as_bytes()));
+            assert_eq!(Ok(data.clone()), dearmor(text.as_bytes()));
+
+            let pasted = format!("\n  {}\n", text.replace('\n', "\r\n"));
+            assert_eq!(Ok(data.clone()), dearmor(pasted.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn damage_is_detected() {
+        let text = armor(b"There and back again", Encoding::Base64);
+
+        let flipped = text.replacen("VGhl", "VGhm", 1);
+        assert!(matches!(
+            dearmor(flipped.as_bytes()),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+        let cut: String = text.lines().take(3).collect::<Vec<_>>().join("\n");
+        assert_eq!(Err(Error::Truncated), dearmor(cut.as_bytes()));
+        assert!(!is_armored(b"HUF\x02"));
+    }
+}
@@ -0,0 +1,171 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::ans::{self, Tables};
+use crate::compress::{decode, encode_mode, source_dictionary, Mode};
+use crate::error::Result;
+use crate::histogram;
+use crate::huffman_tree::strategy::Strategy;
+
+use prettytable::Table;
+
+/// Median timings of each stage. Encoding and decoding time the whole round trip, so they
+/// include counting and computing the code lengths.
+#[derive(Debug)]
+pub struct Measurement {
+    pub file: String,
+    pub mode: Mode,
+    pub strategy: Strategy,
+    pub size: usize,
+    pub compressed_size: usize,
+    pub count: Duration,
+    pub build: Duration,
+    pub encode: Duration,
+    pub decode: Duration,
+}
+
+pub fn measure(
+    file: &str,
+    source: &[u8],
+    mode: Mode,
+    strategy: Strategy,
+    iterations: usize,
+) -> Result<Measurement> {
+    let (tokens, hits) = source_dictionary(source);
+    let encoded = encode_mode(source, mode, strategy);
+    // The checksum in the trailer makes a successful decode a verified round trip.
+    decode(&encoded)?;
+
+    let count = time(iterations, || source_dictionary(source));
+    let build = match mode {
+        Mode::Huffman => time(iterations, || strategy.lengths(&tokens, &hits)),
+        Mode::Ans => {
+            let table_log = ans::table_log(source.len());
+            let histogram = histogram::count(source);
+            time(iterations, || {
+                Tables::new(ans::normalise(&histogram, table_log), table_log)
+            })
+        }
+        // The model is built while coding.
+        Mode::Range => Duration::ZERO,
+    };
+    let encode_time = time(iterations, || encode_mode(source, mode, strategy));
+    let decode_time = time(iterations, || decode(&encoded));
+
+    Ok(Measurement {
+        file: file.to_owned(),
+        mode,
+        strategy,
+        size: source.len(),
+        compressed_size: encoded.len(),
+        count,
+        build,
+        encode: encode_time,
+        decode: decode_time,
+    })
+}
+
+fn time<F, R>(iterations: usize, mut f: F) -> Duration
+where
+    F: FnMut() -> R,
+{
+    let mut timings: Vec<Duration> = (0..iterations.max(1))
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .collect();
+
+    timings.sort();
+    timings[timings.len() / 2]
+}
+
+fn throughput(size: usize, duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    if seconds == 0.0 {
+        return "-".to_string();
+    }
+
+    format!("{:.2}", size as f64 / 1_000_000.0 / seconds)
+}
+
+pub fn print(measurements: &[Measurement]) {
+    let mut table = Table::new();
+    table.add_row(row![
+        "File",
+        "Mode",
+        "Strategy",
+        "Size",
+        "Compressed",
+        "Ratio",
+        "Count MB/s",
+        "Build µs",
+        "Encode MB/s",
+        "Decode MB/s"
+    ]);
+
+    for m in measurements {
+        let ratio = if m.size == 0 {
+            0.0
+        } else {
+            m.compressed_size as f64 / m.size as f64 * 100.0
+        };
+
+        table.add_row(row![
+            m.file,
+            m.mode.name(),
+            match m.mode {
+                Mode::Huffman => m.strategy.name(),
+                Mode::Ans | Mode::Range => "-",
+            },
+            m.size,
+            m.compressed_size,
+            format!("{:.2}%", ratio),
+            throughput(m.size, m.count),
+            m.build.as_micros(),
+            throughput(m.size, m.encode),
+            throughput(m.size, m.decode),
+        ]);
+    }
+
+    table.printstd();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn measures_round_trip() {
+        let input = b"There and back again. A hobbits tale, by Bilbo Baggins";
+        let measurement = measure("hobbit", input, Mode::Huffman, Strategy::Moffat, 3).unwrap();
+
+        assert_eq!(input.len(), measurement.size);
+        assert_eq!(
+            crate::compress::encode_with(input, Strategy::Moffat).len(),
+            measurement.compressed_size
+        );
+    }
+
+    #[test]
+    fn measures_ans() {
+        let input: Vec<u8> = (0..5_000u32).map(|i| (i % 41 / 20) as u8).collect();
+        let measurement = measure("skewed", &input, Mode::Ans, Strategy::default(), 1).unwrap();
+
+        assert_eq!(Mode::Ans, measurement.mode);
+        assert!(measurement.compressed_size < input.len() / 4);
+    }
+
+    #[test]
+    fn median() {
+        let mut calls = 0;
+        let duration = time(3, || {
+            calls += 1;
+            std::thread::sleep(Duration::from_millis(calls));
+        });
+
+        assert_eq!(3, calls);
+        assert!(duration >= Duration::from_millis(2));
+    }
+}
@@ -0,0 +1,345 @@
+//! Files split into blocks that are compressed on their own, so damage to one block loses only
+//! that block. Each block starts with a sync marker and a header protected by its own CRC-32:
+//!
+//! ```text
+//! SYNC | total u64 | offset u64 | length u32 | size u32 | CRC-32 of the fields | frame
+//! ```
+//!
+//! `total` is the length of the original, so any intact block tells how much there is to
+//! recover. `offset` and `length` place the block's bytes in the original and `size` is the
+//! length of the frame that follows, a complete compressed file with its own checksum.
+//! `recover` finds blocks by their sync markers, so it can resync after bytes that are damaged,
+//! lost or added.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::bytes::{read_be_u32, read_be_usize};
+use crate::compress::decode as decode_frame;
+use crate::error::{Error, Result};
+use crate::format::{read_frame, write_frame, Method};
+
+/// Marks the start of a block. Random bytes, so it is unlikely to appear by chance in a payload.
+pub const SYNC: [u8; 8] = [0xb5, 0x1c, 0x7e, 0x0d, 0xa9, 0x42, 0xe6, 0x93];
+const FIELDS_SIZE: usize = 8 + 8 + 4 + 4;
+const HEADER_SIZE: usize = SYNC.len() + FIELDS_SIZE + 4;
+/// Small enough that a block stored as-is, with its frame, still fits the `u32` size field.
+pub const MAX_BLOCK_SIZE: usize = 1 << 31;
+
+struct Header {
+    total: usize,
+    offset: usize,
+    length: usize,
+    size: usize,
+}
+
+impl Header {
+    fn write(&self) -> Vec<u8> {
+        let fields = [
+            &(self.total as u64).to_be_bytes()[..],
+            &(self.offset as u64).to_be_bytes(),
+            &(self.length as u32).to_be_bytes(),
+            &(self.size as u32).to_be_bytes(),
+        ]
+        .concat();
+        let checksum = crc32fast::hash(&fields).to_be_bytes();
+
+        [&SYNC[..], &fields, &checksum].concat()
+    }
+
+    /// The header at the start of the input, if it has the sync marker and its checksum holds.
+    fn read(input: &[u8]) -> Option<Self> {
+        let fields = input
+            .strip_prefix(&SYNC[..])?
+            .get(..HEADER_SIZE - SYNC.len())?;
+        let (total, remaining) = read_be_usize(fields)?;
+        let (offset, remaining) = read_be_usize(remaining)?;
+        let (length, remaining) = read_be_u32(remaining)?;
+        let (size, remaining) = read_be_u32(remaining)?;
+        let (checksum, _) = read_be_u32(remaining)?;
+        if crc32fast::hash(&fields[..FIELDS_SIZE]) != checksum {
+            return None;
+        }
+
+        Some(Self {
+            total,
+            offset,
+            length: length as usize,
+            size: size as usize,
+        })
+    }
+}
+
+/// Splits the source into blocks of `block_size` bytes and compresses each with `encode`.
+pub fn encode<F>(source: &[u8], block_size: usize, encode: F) -> Vec<u8>
+where
+    F: Fn(&[u8]) -> Vec<u8>,
+{
+    assert!((1..=MAX_BLOCK_SIZE).contains(&block_size));
+
+    let mut body = Vec::with_capacity(source.len() / 2);
+    for (i, chunk) in source.chunks(block_size).enumerate() {
+        let frame = encode(chunk);
+        let header = Header {
+            total: source.len(),
+            offset: i * block_size,
+            length: chunk.len(),
+            size: frame.len(),
+        };
+        body.extend(header.write());
+        body.extend(frame);
+    }
+
+    write_frame(Method::Blocks, body, source)
+}
+
+/// Decodes every block of an intact body, in order.
+pub fn decode(body: &[u8]) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut remaining = body;
+
+    while !remaining.is_empty() {
+        let header = Header::read(remaining).ok_or(Error::InvalidBlock)?;
+        if header.offset != output.len()
+            || header.offset.saturating_add(header.length) > header.total
+        {
+            return Err(Error::InvalidBlock);
+        }
+        let frame = remaining
+            .get(HEADER_SIZE..HEADER_SIZE + header.size)
+            .ok_or(Error::Truncated)?;
+
+        let block = decode_block(frame)?;
+        if block.len() != header.length {
+            return Err(Error::LengthMismatch {
+                expected: header.length,
+                actual: block.len(),
+            });
+        }
+        output.extend(block);
+        remaining = &remaining[HEADER_SIZE + header.size..];
+    }
+
+    Ok(output)
+}
+
+/// Blocks may not nest, which would let a crafted file recurse for every block header.
+fn decode_block(frame: &[u8]) -> Result<Vec<u8>> {
+    if read_frame(frame)?.method == Method::Blocks {
+        return Err(Error::InvalidBlock);
+    }
+
+    decode_frame(frame)
+}
+
+/// What could be read back from a damaged file.
+#[derive(Debug, PartialEq)]
+pub struct Recovery {
+    /// The original up to the end of the last intact block, with the lost ranges before it
+    /// filled with zeros.
+    pub data: Vec<u8>,
+    /// Ranges of the original that could not be decoded, in order. The last one runs past the
+    /// end of `data` when the blocks at the end were lost.
+    pub lost: Vec<Range<usize>>,
+    /// The length of the original, as the block headers give it.
+    pub total: usize,
+}
+
+/// Decodes the source as usual if it is intact, or else every block that is. Fails when the
+/// source is damaged and has no intact block header to go on, e.g. a file without blocks.
+pub fn recover(source: &[u8]) -> Result<Recovery> {
+    let err = match decode_frame(source) {
+        Ok(data) => {
+            return Ok(Recovery {
+                total: data.len(),
+                data,
+                lost: Vec::new(),
+            })
+        }
+        Err(err) => err,
+    };
+
+    let mut blocks = BTreeMap::new();
+    let mut total = None;
+    let mut position = 0;
+    while let Some(found) = find_sync(&source[position..]) {
+        let start = position + found;
+        position = start + 1;
+        let header = match Header::read(&source[start..]) {
+            Some(header) => header,
+            None => continue,
+        };
+        // Headers that disagree on the total cannot all be intact, the first one found wins.
+        let total = *total.get_or_insert(header.total);
+        if header.total != total || header.offset.saturating_add(header.length) > total {
+            continue;
+        }
+
+        let block = source
+            .get(start + HEADER_SIZE..start + HEADER_SIZE + header.size)
+            .and_then(|frame| decode_block(frame).ok())
+            .filter(|block| block.len() == header.length);
+        if let Some(block) = block {
+            position = start + HEADER_SIZE + header.size;
+            blocks.insert(header.offset, block);
+        }
+    }
+    let total = total.ok_or(err)?;
+
+    // A forged header can claim any total or offset, so allocate only up to the blocks found,
+    // and drop blocks placed further than the source has room for headers before them.
+    let block_size = blocks.values().map(Vec::len).max().unwrap_or_default();
+    let slots = source.len() / HEADER_SIZE;
+    blocks.retain(|offset, _| *offset <= slots.saturating_mul(block_size));
+    let end = blocks
+        .iter()
+        .map(|(offset, block)| offset + block.len())
+        .max()
+        .unwrap_or_default();
+
+    let mut data = vec![0; end];
+    let mut lost = Vec::new();
+    let mut covered = 0;
+    for (offset, block) in blocks {
+        if offset < covered {
+            continue;
+        }
+        if offset > covered {
+            lost.push(covered..offset);
+        }
+        data[offset..offset + block.len()].copy_from_slice(&block);
+        covered = offset + block.len();
+    }
+    if covered < total {
+        lost.push(covered..total);
+    }
+
+    Ok(Recovery { data, lost, total })
+}
+
+fn find_sync(input: &[u8]) -> Option<usize> {
+    input.windows(SYNC.len()).position(|window| window == SYNC)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compress::encode;
+
+    const BLOCK: usize = 1000;
+
+    fn source() -> Vec<u8> {
+        (0..10 * BLOCK as u32 + 123)
+            .map(|i| b"abracadabra"[(i * i % 11) as usize])
+            .collect()
+    }
+
+    /// Where the frame of the block at `index` starts.
+    fn block_start(encoded: &[u8], index: usize) -> usize {
+        let mut position = 0;
+        for _ in 0..=index {
+            position += find_sync(&encoded[position..]).unwrap() + 1;
+        }
+        position - 1 + HEADER_SIZE
+    }
+
+    #[test]
+    fn round_trip() {
+        let source = source();
+        let encoded = super::encode(&source, BLOCK, encode);
+
+        assert_eq!(Method::Blocks, read_frame(&encoded).unwrap().method);
+        assert_eq!(Ok(source.clone()), decode_frame(&encoded));
+        assert_eq!(
+            Ok(Vec::new()),
+            decode_frame(&super::encode(b"", BLOCK, encode))
+        );
+
+        let recovery = recover(&encoded).unwrap();
+        assert_eq!(source, recovery.data);
+        assert!(recovery.lost.is_empty());
+    }
+
+    #[test]
+    fn damaged_blocks_are_skipped() {
+        let source = source();
+        let mut encoded = super::encode(&source, BLOCK, encode);
+        // A payload byte of the second block, and the header of the fifth.
+        let second = block_start(&encoded, 1) + 20;
+        let fifth = block_start(&encoded, 4) - 10;
+        encoded[second] ^= 0x10;
+        encoded[fifth] ^= 0x10;
+        assert!(decode_frame(&encoded).is_err());
+
+        let recovery = recover(&encoded).unwrap();
+        assert_eq!(vec![BLOCK..2 * BLOCK, 4 * BLOCK..5 * BLOCK], recovery.lost);
+        assert_eq!(source.len(), recovery.data.len());
+        assert_eq!(source[..BLOCK], recovery.data[..BLOCK]);
+        assert!(recovery.data[BLOCK..2 * BLOCK].iter().all(|b| *b == 0));
+        assert_eq!(source[5 * BLOCK..], recovery.data[5 * BLOCK..]);
+    }
+
+    #[test]
+    fn resyncs_after_lost_bytes() {
+        let source = source();
+        let encoded = super::encode(&source, BLOCK, encode);
+        // Bytes cut from the third block, and the end of the last block with the trailer.
+        let third = block_start(&encoded, 2) + 30;
+        let cut = [&encoded[..third], &encoded[third + 7..encoded.len() - 20]].concat();
+
+        let recovery = recover(&cut).unwrap();
+        assert_eq!(
+            vec![2 * BLOCK..3 * BLOCK, 10 * BLOCK..source.len()],
+            recovery.lost
+        );
+        assert_eq!(source.len(), recovery.total);
+        assert_eq!(10 * BLOCK, recovery.data.len());
+        assert_eq!(
+            source[3 * BLOCK..10 * BLOCK],
+            recovery.data[3 * BLOCK..10 * BLOCK]
+        );
+    }
+
+    fn forged_block(total: usize, offset: usize) -> Vec<u8> {
+        let frame = encode(b"abc");
+        let header = Header {
+            total,
+            offset,
+            length: 3,
+            size: frame.len(),
+        };
+        [header.write(), frame].concat()
+    }
+
+    #[test]
+    fn header_total_is_reported_not_allocated() {
+        let recovery = recover(&forged_block(1 << 50, 0)).unwrap();
+        assert_eq!(b"abc".to_vec(), recovery.data);
+        assert_eq!(1 << 50, recovery.total);
+        assert_eq!(vec![3..1 << 50], recovery.lost);
+    }
+
+    #[test]
+    fn offsets_beyond_the_source_are_dropped() {
+        let recovery = recover(&forged_block(1 << 50, 1 << 49)).unwrap();
+        assert!(recovery.data.is_empty());
+        assert_eq!(vec![0..1 << 50], recovery.lost);
+    }
+
+    #[test]
+    fn files_without_blocks_are_not_recovered() {
+        let mut encoded = encode(&source());
+        let middle = encoded.len() / 2;
+        encoded[middle] ^= 1;
+
+        assert!(recover(&encoded).is_err());
+    }
+
+    #[test]
+    fn nested_blocks_are_rejected() {
+        let inner = |chunk: &[u8]| super::encode(chunk, BLOCK, encode);
+        let nested = super::encode(&source(), 4 * BLOCK, inner);
+
+        assert_eq!(Err(Error::InvalidBlock), decode_frame(&nested));
+    }
+}
@@ -2,6 +2,122 @@ use std::convert::TryInto;
 
 pub type Codes = Vec<bool>;
 
+/// A code packed into an integer, the first bit most significant. Trees built from `usize`
+/// counts are at most 92 levels deep, well within the 128 bits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Code {
+    pub bits: u128,
+    pub len: usize,
+}
+
+impl Code {
+    pub const MAX_LEN: usize = 128;
+
+    /// The code with one more bit appended.
+    pub fn push(self, bit: bool) -> Self {
+        assert!(self.len < Self::MAX_LEN, "codes are at most 128 bits");
+        Self {
+            bits: self.bits << 1 | bit as u128,
+            len: self.len + 1,
+        }
+    }
+
+    pub fn to_codes(self) -> Codes {
+        (0..self.len)
+            .rev()
+            .map(|i| self.bits >> i & 1 == 1)
+            .collect()
+    }
+}
+
+/// Where in a byte the first of its bits goes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BitOrder {
+    /// The lowest bit first, as huff and DEFLATE pack bits.
+    #[default]
+    LsbFirst,
+    /// The highest bit first, as JPEG and most hardware decoders expect.
+    MsbFirst,
+}
+
+/// The bit a step to the left child is written as. Left is `true` in `Codes`, and canonical
+/// codes count with it as one, so `LeftIsOne` writes canonical codes the way DEFLATE and JPEG
+/// define them. `LeftIsZero` inverts every bit.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Polarity {
+    #[default]
+    LeftIsOne,
+    LeftIsZero,
+}
+
+impl Polarity {
+    /// The bit written for a step, `true` being left.
+    pub const fn bit(self, left: bool) -> bool {
+        match self {
+            Self::LeftIsOne => left,
+            Self::LeftIsZero => !left,
+        }
+    }
+
+    /// The digit a step is shown as, matching the bit written.
+    pub const fn digit(self, left: bool) -> char {
+        if self.bit(left) {
+            '1'
+        } else {
+            '0'
+        }
+    }
+}
+
+/// How codes are laid out in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BitFormat {
+    pub order: BitOrder,
+    pub polarity: Polarity,
+}
+
+impl BitFormat {
+    /// The layout of huff's own payloads.
+    pub const HUFF: Self = Self {
+        order: BitOrder::LsbFirst,
+        polarity: Polarity::LeftIsOne,
+    };
+
+    /// Packs the steps into bytes, padding the last byte with zero bits.
+    pub fn pack(self, codes: &[bool]) -> Vec<u8> {
+        codes
+            .chunks(8)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, left)| self.polarity.bit(**left))
+                    .fold(0, |byte, (i, _)| byte | self.mask(i))
+            })
+            .collect()
+    }
+
+    /// The first `size` steps packed in the bytes, or as many as there are.
+    pub fn unpack(self, bytes: &[u8], size: usize) -> Codes {
+        let bytes = &bytes[..bytes.len().min(size.div_ceil(8))];
+        let mut codes: Codes = bytes
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| self.polarity.bit(byte & self.mask(i) != 0)))
+            .collect();
+
+        codes.truncate(size);
+        codes
+    }
+
+    /// The bit of a byte that holds its `i`th bit.
+    fn mask(self, i: usize) -> u8 {
+        match self.order {
+            BitOrder::LsbFirst => 1 << i,
+            BitOrder::MsbFirst => 0x80 >> i,
+        }
+    }
+}
+
 pub fn bytes_from(bits: Codes) -> Vec<u8> {
     bits.chunks(8).map(bools_to_bits).collect()
 }
@@ -20,7 +136,8 @@ fn bools_to_bits(chunk: &[bool]) -> u8 {
     byte
 }
 
-pub fn codes_from(bytes: &[u8], _size: usize) -> Vec<bool> {
+pub fn codes_from(bytes: &[u8], size: usize) -> Vec<bool> {
+    let bytes = &bytes[..bytes.len().min(size.div_ceil(8))];
     let mut codes = Vec::with_capacity(bytes.len() * 8);
 
     for byte in bytes {
@@ -29,7 +146,8 @@ pub fn codes_from(bytes: &[u8], _size: usize) -> Vec<bool> {
         }
     }
 
-    codes[.._size].to_vec()
+    codes.truncate(size);
+    codes
 }
 
 fn byte_to_bools(byte: u8) -> [bool; 8] {
@@ -43,8 +161,7 @@ fn byte_to_bools(byte: u8) -> [bool; 8] {
 
 pub fn usize_to_bytes(v: Vec<usize>) -> Vec<u8> {
     v.into_iter()
-        .map(|u| u.to_be_bytes().to_vec())
-        .flatten()
+        .flat_map(|u| u.to_be_bytes().to_vec())
         .collect()
 }
 
@@ -52,13 +169,12 @@ pub fn usize_to_smallest_bytes(v: Vec<usize>) -> (u8, Vec<u8>) {
     let byte_size = smallest_byte_representation(&v);
     let bytes = v
         .into_iter()
-        .map(|u| match byte_size {
+        .flat_map(|u| match byte_size {
             8 => (u as u8).to_be_bytes().to_vec(),
             16 => (u as u16).to_be_bytes().to_vec(),
             32 => (u as u32).to_be_bytes().to_vec(),
             _ => u.to_be_bytes().to_vec(),
         })
-        .flatten()
         .collect();
 
     (byte_size, bytes)
@@ -94,20 +210,108 @@ pub fn bytes_to_usize(bit_count: usize, v: &[u8]) -> Vec<usize> {
         .collect()
 }
 
-pub fn read_be_usize(input: &[u8]) -> (usize, &[u8]) {
+pub fn read_be_usize(input: &[u8]) -> Option<(usize, &[u8])> {
+    if input.len() < std::mem::size_of::<usize>() {
+        return None;
+    }
+
     let (int_bytes, remaining) = input.split_at(std::mem::size_of::<usize>());
-    (
-        usize::from_be_bytes(int_bytes.try_into().unwrap()),
-        remaining,
-    )
+    Some((usize::from_be_bytes(int_bytes.try_into().ok()?), remaining))
+}
+
+/// LEB128: seven bits per byte, least significant group first, high bit set on all but the last.
+pub fn usize_to_varint(mut u: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(2);
+    while u >= 0x80 {
+        bytes.push((u as u8 & 0x7f) | 0x80);
+        u >>= 7;
+    }
+    bytes.push(u as u8);
+
+    bytes
+}
+
+/// The most bytes a varint of a usize takes.
+pub const MAX_VARINT: usize = usize::BITS.div_ceil(7) as usize;
+
+/// A varint padded with empty groups to `MAX_VARINT` bytes, so it can be overwritten in place
+/// once the value is known.
+pub fn usize_to_padded_varint(u: usize) -> Vec<u8> {
+    let mut bytes = usize_to_varint(u);
+    let last = bytes.len() - 1;
+    bytes[last] |= 0x80;
+    bytes.resize(MAX_VARINT, 0x80);
+    bytes[MAX_VARINT - 1] &= 0x7f;
+
+    bytes
+}
+
+pub fn read_varint(input: &[u8]) -> Option<(usize, &[u8])> {
+    let mut u: usize = 0;
+
+    for (i, byte) in input.iter().enumerate() {
+        let group = (*byte & 0x7f) as usize;
+        let shift = 7 * i as u32;
+        if shift >= usize::BITS || (group << shift) >> shift != group {
+            return None;
+        }
+
+        u |= group << shift;
+        if byte & 0x80 == 0 {
+            return Some((u, &input[i + 1..]));
+        }
+    }
+
+    None
 }
 
-pub fn read_be_u8(input: &[u8]) -> (usize, &[u8]) {
-    let (int_bytes, remaining) = input.split_at(std::mem::size_of::<u8>());
-    (
-        usize::from_be_bytes([0, 0, 0, 0, 0, 0, 0, int_bytes[0]]),
-        remaining,
-    )
+/// Appends the low `count` bits of `value`, least significant first.
+pub fn push_bits(bits: &mut Codes, value: usize, count: usize) {
+    bits.extend((0..count).map(|i| value >> i & 1 == 1));
+}
+
+/// Reads bits in the order `bytes_from` packs them.
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.position / 8)?;
+        let bit = byte & (1 << (self.position % 8)) != 0;
+        self.position += 1;
+
+        Some(bit)
+    }
+
+    /// Reads `count` bits written by `push_bits`.
+    pub fn read_bits(&mut self, count: usize) -> Option<usize> {
+        (0..count).try_fold(0, |value, i| Some(value | (self.read_bit()? as usize) << i))
+    }
+
+    /// Bytes started so far, including a partially read one.
+    pub fn bytes_read(&self) -> usize {
+        self.position.div_ceil(8)
+    }
+}
+
+pub fn read_be_u32(input: &[u8]) -> Option<(u32, &[u8])> {
+    if input.len() < std::mem::size_of::<u32>() {
+        return None;
+    }
+
+    let (int_bytes, remaining) = input.split_at(std::mem::size_of::<u32>());
+    Some((u32::from_be_bytes(int_bytes.try_into().ok()?), remaining))
+}
+
+pub fn read_be_u8(input: &[u8]) -> Option<(usize, &[u8])> {
+    let (int_byte, remaining) = input.split_first()?;
+    Some((*int_byte as usize, remaining))
 }
 
 #[cfg(test)]
@@ -123,4 +327,89 @@ mod test {
 
         assert_eq!(expected, actual.as_slice());
     }
+
+    #[test]
+    fn codes_from_clamps_to_available_bytes() {
+        assert_eq!(16, codes_from(b"ab", usize::MAX).len());
+        assert_eq!(3, codes_from(b"ab", 3).len());
+    }
+
+    #[test]
+    fn code_to_codes() {
+        let code = Code::default().push(true).push(false).push(false);
+
+        assert_eq!(
+            Code {
+                bits: 0b100,
+                len: 3
+            },
+            code
+        );
+        assert_eq!(vec![true, false, false], code.to_codes());
+    }
+
+    #[test]
+    fn bit_formats() {
+        // Left, right, right, left, left.
+        let codes = vec![true, false, false, true, true];
+        let msb = BitFormat {
+            order: BitOrder::MsbFirst,
+            polarity: Polarity::LeftIsOne,
+        };
+        let inverted = BitFormat {
+            order: BitOrder::LsbFirst,
+            polarity: Polarity::LeftIsZero,
+        };
+
+        assert_eq!(vec![0b0001_1001], BitFormat::HUFF.pack(&codes));
+        assert_eq!(bytes_from(codes.clone()), BitFormat::HUFF.pack(&codes));
+        assert_eq!(vec![0b1001_1000], msb.pack(&codes));
+        assert_eq!(vec![0b0000_0110], inverted.pack(&codes));
+
+        for format in [BitFormat::HUFF, msb, inverted] {
+            assert_eq!(codes, format.unpack(&format.pack(&codes), codes.len()));
+        }
+        assert_eq!('0', Polarity::LeftIsZero.digit(true));
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        for u in [0, 1, 127, 128, 300, u32::MAX as usize, usize::MAX] {
+            let bytes = usize_to_varint(u);
+            assert_eq!(Some((u, &[][..])), read_varint(&bytes));
+        }
+        assert_eq!(1, usize_to_varint(127).len());
+        assert_eq!(None, read_varint(&[0x80]));
+        assert_eq!(None, read_varint(&[0xff; 11]));
+    }
+
+    #[test]
+    fn padded_varint_round_trip() {
+        for u in [0, 1, 300, usize::MAX] {
+            let bytes = usize_to_padded_varint(u);
+            assert_eq!(MAX_VARINT, bytes.len());
+            assert_eq!(Some((u, &[][..])), read_varint(&bytes));
+        }
+    }
+
+    #[test]
+    fn bit_reader_reads_pushed_bits() {
+        let mut bits = Vec::new();
+        push_bits(&mut bits, 5, 3);
+        push_bits(&mut bits, 300, 9);
+        let bytes = bytes_from(bits);
+
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(Some(5), reader.read_bits(3));
+        assert_eq!(Some(300), reader.read_bits(9));
+        assert_eq!(2, reader.bytes_read());
+        assert_eq!(None, reader.read_bits(8));
+    }
+
+    #[test]
+    fn read_be_usize_short_input() {
+        assert_eq!(None, read_be_usize(&[0; 7]));
+        assert_eq!(Some((1, &[][..])), read_be_usize(&[0, 0, 0, 0, 0, 0, 0, 1]));
+        assert_eq!(None, read_be_u8(&[]));
+    }
 }
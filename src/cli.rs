@@ -1,10 +1,67 @@
 use clap::{App, Arg, ArgMatches, SubCommand};
 
+use huff::blocks::MAX_BLOCK_SIZE;
+use huff::compress::MODES;
+use huff::huffman_tree::strategy::STRATEGIES;
+use huff::{armor, export, stats};
+
+fn format_arg() -> Arg<'static, 'static> {
+    Arg::with_name("format")
+        .long("format")
+        .value_name("FORMAT")
+        .help("Output format of the statistics.")
+        .possible_values(stats::FORMATS)
+        .default_value("table")
+}
+
+fn percent(value: String) -> Result<(), String> {
+    match value.parse::<u8>() {
+        Ok(1..=100) => Ok(()),
+        _ => Err(format!(
+            "expected a percentage from 1 to 100, got {}",
+            value
+        )),
+    }
+}
+
+/// A size in bytes, with an optional K, M or G suffix for KiB, MiB or GiB.
+pub fn parse_size(value: &str) -> Option<usize> {
+    let (digits, shift) = match value.char_indices().last()? {
+        (i, 'K') | (i, 'k') => (&value[..i], 10),
+        (i, 'M') | (i, 'm') => (&value[..i], 20),
+        (i, 'G') | (i, 'g') => (&value[..i], 30),
+        _ => (value, 0),
+    };
+
+    digits.parse::<usize>().ok()?.checked_mul(1 << shift)
+}
+
+fn block_size(value: String) -> Result<(), String> {
+    match parse_size(&value) {
+        Some(size) if (1..=MAX_BLOCK_SIZE).contains(&size) => Ok(()),
+        _ => Err(format!(
+            "expected a size from 1 byte to 2G, e.g. 1M, got {}",
+            value
+        )),
+    }
+}
+
 pub fn app() -> ArgMatches<'static> {
+    definition().get_matches()
+}
+
+/// The arguments `app` parses, apart so tests can parse their own.
+pub fn definition() -> App<'static, 'static> {
     App::new("huff")
         .version(crate_version!())
         .author(crate_authors!())
         .about("(De)Compress files with huffman trees.")
+        .arg(
+            Arg::with_name("files")
+                .value_name("FILE")
+                .help("Files to (de)compress. Reads stdin and writes stdout when omitted.")
+                .multiple(true),
+        )
         .arg(
             Arg::with_name("filepath")
                 .short("f")
@@ -22,19 +79,264 @@ pub fn app() -> ArgMatches<'static> {
         )
         .arg(
             Arg::with_name("stats")
-                .short("stats")
+                .short("s")
                 .long("stats")
                 .help("Encodes source to produce compression statitics.")
                 .takes_value(false),
         )
+        .arg(format_arg())
+        .arg(
+            Arg::with_name("stdout")
+                .short("c")
+                .long("stdout")
+                .help("Write to stdout and keep the input files.")
+                .conflicts_with("output")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("keep")
+                .short("k")
+                .long("keep")
+                .help("Keep the input files instead of removing them.")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .help("Write the output to FILE instead of deriving the name from the input.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .help("Overwrite existing output files.")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("strategy")
+                .long("strategy")
+                .value_name("STRATEGY")
+                .help(
+                    "How code lengths are computed. Codes are equally small either way, but \
+                     may differ when counts tie.",
+                )
+                .possible_values(STRATEGIES)
+                .default_value("two-queue"),
+        )
+        .arg(
+            Arg::with_name("mode")
+                .long("mode")
+                .value_name("MODE")
+                .help("The entropy coder to compress with.")
+                .possible_values(MODES)
+                .default_value("huffman"),
+        )
+        .arg(
+            Arg::with_name("sample")
+                .long("sample")
+                .value_name("PERCENT")
+                .help("Builds the code from evenly spaced chunks making up PERCENT of the input.")
+                .validator(percent),
+        )
+        .arg(
+            Arg::with_name("armor")
+                .long("armor")
+                .value_name("ENCODING")
+                .help("Writes the compressed file as text, base64 unless ENCODING is given.")
+                .possible_values(armor::ENCODINGS)
+                .min_values(0)
+                .require_equals(true),
+        )
+        .arg(
+            Arg::with_name("block-size")
+                .long("block-size")
+                .value_name("SIZE")
+                .help("Compresses blocks of SIZE bytes separately, so damage loses one block.")
+                .validator(block_size),
+        )
+        .arg(
+            Arg::with_name("parity")
+                .long("parity")
+                .value_name("PERCENT")
+                .help(
+                    "Appends Reed-Solomon parity worth PERCENT of the file to repair damage with.",
+                )
+                .validator(percent),
+        )
+        .arg(
+            Arg::with_name("encrypt")
+                .long("encrypt")
+                .help("Encrypts the compressed file with a passphrase.")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("passphrase-file")
+                .long("passphrase-file")
+                .value_name("FILE")
+                .help("Reads the passphrase from the first line of FILE instead of the terminal.")
+                .global(true)
+                .takes_value(true),
+        )
         .subcommand(
             SubCommand::with_name("stats")
                 .help("Retrieves statistics on the compressed file.")
                 .arg(
                     Arg::with_name("file")
-                        .help("The file to get stats from")
+                        .help("The file to get stats from. Reads stdin when omitted.")
                         .takes_value(true),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Compares the symbol counts and code lengths of two compressed files.")
+                .arg(
+                    Arg::with_name("a")
+                        .value_name("A")
+                        .help("The file whose data is coded with the other file's code.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("b")
+                        .value_name("B")
+                        .help("The file to compare against.")
+                        .required(true),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("Measures the throughput and ratio of each stage over several iterations.")
+                .arg(
+                    Arg::with_name("files")
+                        .value_name("FILE")
+                        .help("The files to benchmark.")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("iterations")
+                        .short("i")
+                        .long("iterations")
+                        .value_name("N")
+                        .help("How many times to run each stage. The median is reported.")
+                        .default_value("5"),
+                )
+                .arg(
+                    Arg::with_name("mode")
+                        .short("m")
+                        .long("mode")
+                        .value_name("MODE")
+                        .help("Modes to compare side-by-side.")
+                        .possible_values(MODES)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .default_value("huffman"),
+                )
+                .arg(
+                    Arg::with_name("strategy")
+                        .short("t")
+                        .long("strategy")
+                        .value_name("STRATEGY")
+                        .help("Tree construction strategies to compare side-by-side.")
+                        .possible_values(STRATEGIES)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .default_value("two-queue"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("explain")
+                .about("Traces the tree construction, and the coding of the first bytes of a file.")
+                .arg(
+                    Arg::with_name("file")
+                        .help("The uncompressed file to explain. Reads stdin when omitted.")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("bytes")
+                        .short("n")
+                        .long("bytes")
+                        .value_name("N")
+                        .help("How many bytes to encode and decode.")
+                        .default_value("16"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Output format of the trace.")
+                        .possible_values(&["text", "json"])
+                        .default_value("text"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("explore")
+                .about("Explores the tree, codes and bitstream of a file in the terminal.")
+                .arg(
+                    Arg::with_name("file")
+                        .help("The file to explore, compressed or not.")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("test")
+                .about("Verifies compressed files without writing the decoded output.")
+                .arg(
+                    Arg::with_name("files")
+                        .value_name("FILE")
+                        .help("The files to verify. Reads stdin when omitted.")
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("recover")
+                .about("Decodes the intact blocks of a damaged file and reports the ranges lost.")
+                .arg(
+                    Arg::with_name("file")
+                        .help("The damaged file.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Write to FILE instead of deriving the name from the input.")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("stdout")
+                        .short("c")
+                        .long("stdout")
+                        .help("Write the recovered data to stdout.")
+                        .conflicts_with("output")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Overwrite an existing output file.")
+                        .takes_value(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("tree")
+                .about("Exports the huffman tree of a file, or of a compressed file's header.")
+                .arg(
+                    Arg::with_name("file")
+                        .help("The file to build the tree from. Reads stdin when omitted.")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Output format of the tree.")
+                        .possible_values(export::FORMATS)
+                        .default_value("dot"),
                 ),
         )
-        .get_matches()
 }
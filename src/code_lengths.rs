@@ -0,0 +1,231 @@
+//! The code length of every byte value, written the way DEFLATE writes its code lengths: runs
+//! of equal lengths collapse into repeat symbols, so absent ranges of bytes cost a few bits,
+//! and those symbols are themselves huffman coded with canonical codes.
+
+use crate::bytes::{push_bits, BitReader, Code, Codes};
+use crate::error::{Error, Result};
+use crate::huffman_tree::{canonical, tree};
+
+pub const SYMBOLS: usize = 256;
+
+/// Lengths below 16 are written as themselves.
+const LITERALS: usize = 16;
+/// The previous length 3 to 6 more times.
+const REPEAT: usize = 16;
+/// 3 to 10 zero lengths.
+const ZEROS: usize = 17;
+/// 11 to 138 zero lengths.
+const LONG_ZEROS: usize = 18;
+/// A length of 16 or more, in the next 8 bits. Lengths over `Code::MAX_LEN` are invalid.
+const ESCAPE: usize = 19;
+const ALPHABET: usize = 20;
+
+/// At most 256 symbols are written, which keeps their codes shorter than 16 bits.
+const LENGTH_BITS: usize = 4;
+
+fn extra_bits(symbol: usize) -> usize {
+    match symbol {
+        REPEAT => 2,
+        ZEROS => 3,
+        LONG_ZEROS => 7,
+        ESCAPE => 8,
+        _ => 0,
+    }
+}
+
+/// Writes the lengths, indexed by byte value.
+pub fn write(lengths: &[usize]) -> Codes {
+    let runs = runs(lengths);
+
+    let mut counts = [0; ALPHABET];
+    runs.iter().for_each(|(symbol, _)| counts[*symbol] += 1);
+    let mut used: Vec<(usize, usize)> = (0..ALPHABET)
+        .filter(|s| counts[*s] > 0)
+        .map(|s| (s, counts[s]))
+        .collect();
+    used.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+    let (symbols, hits): (Vec<usize>, Vec<usize>) = used.into_iter().unzip();
+
+    let mut code_lengths = [0; ALPHABET];
+    if let Some(tree) = tree::with_vecdeque(&symbols, &hits, runs.len()) {
+        for (symbol, code) in tree.stream_codes() {
+            debug_assert!(code.len < 1 << LENGTH_BITS);
+            code_lengths[symbol] = code.len;
+        }
+    }
+    let lengths: Vec<(usize, usize)> = code_lengths.iter().copied().enumerate().collect();
+    let mut codes: Vec<Codes> = vec![Codes::new(); ALPHABET];
+    for (symbol, code) in canonical::codes(&lengths).unwrap_or_default() {
+        codes[symbol] = code;
+    }
+
+    let mut bits = Codes::new();
+    code_lengths
+        .iter()
+        .for_each(|length| push_bits(&mut bits, *length, LENGTH_BITS));
+    for (symbol, extra) in runs {
+        bits.extend_from_slice(&codes[symbol]);
+        push_bits(&mut bits, extra, extra_bits(symbol));
+    }
+
+    bits
+}
+
+/// Reads the lengths of all 256 byte values.
+pub fn read(reader: &mut BitReader) -> Result<Vec<usize>> {
+    let code_lengths = (0..ALPHABET)
+        .map(|symbol| {
+            Ok((
+                symbol,
+                reader.read_bits(LENGTH_BITS).ok_or(Error::Truncated)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let codes = canonical::codes(&code_lengths).ok_or(Error::InvalidDictionary)?;
+
+    let mut lengths = Vec::with_capacity(SYMBOLS);
+    while lengths.len() < SYMBOLS {
+        let symbol = read_symbol(reader, &codes)?;
+        let extra = reader
+            .read_bits(extra_bits(symbol))
+            .ok_or(Error::Truncated)?;
+
+        let (length, count) = match symbol {
+            REPEAT => (*lengths.last().ok_or(Error::InvalidDictionary)?, extra + 3),
+            ZEROS => (0, extra + 3),
+            LONG_ZEROS => (0, extra + 11),
+            ESCAPE if extra > Code::MAX_LEN => return Err(Error::InvalidDictionary),
+            ESCAPE => (extra, 1),
+            literal => (literal, 1),
+        };
+        if lengths.len() + count > SYMBOLS {
+            return Err(Error::InvalidDictionary);
+        }
+        lengths.resize(lengths.len() + count, length);
+    }
+
+    Ok(lengths)
+}
+
+fn read_symbol(reader: &mut BitReader, codes: &[(usize, Codes)]) -> Result<usize> {
+    let mut code = Codes::new();
+
+    while code.len() < 1 << LENGTH_BITS {
+        code.push(reader.read_bit().ok_or(Error::Truncated)?);
+        if let Some((symbol, _)) = codes.iter().find(|(_, c)| *c == code) {
+            return Ok(*symbol);
+        }
+    }
+
+    Err(Error::InvalidDictionary)
+}
+
+/// Run-length encodes the lengths into symbols and the value of their extra bits.
+fn runs(lengths: &[usize]) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut i = 0;
+
+    while i < lengths.len() {
+        let length = lengths[i];
+        let run = lengths[i..].iter().take_while(|l| **l == length).count();
+
+        if length == 0 && run >= 11 {
+            let count = run.min(138);
+            runs.push((LONG_ZEROS, count - 11));
+            i += count;
+        } else if length == 0 && run >= 3 {
+            let count = run.min(10);
+            runs.push((ZEROS, count - 3));
+            i += count;
+        } else {
+            runs.push(if length < LITERALS {
+                (length, 0)
+            } else {
+                (ESCAPE, length)
+            });
+            i += 1;
+
+            let mut rest = if length > 0 { run - 1 } else { 0 };
+            while rest >= 3 {
+                let count = rest.min(6);
+                runs.push((REPEAT, count - 3));
+                i += count;
+                rest -= count;
+            }
+        }
+    }
+
+    runs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bytes::bytes_from;
+
+    fn round_trip(lengths: &[usize]) -> (usize, Vec<usize>) {
+        let bytes = bytes_from(write(lengths));
+        let mut reader = BitReader::new(&bytes);
+
+        (bytes.len(), read(&mut reader).unwrap())
+    }
+
+    #[test]
+    fn sparse_alphabet_is_small() {
+        let mut lengths = vec![0; SYMBOLS];
+        lengths[b'a' as usize] = 1;
+        lengths[b'b' as usize] = 2;
+        lengths[b'\n' as usize] = 2;
+
+        let (size, read) = round_trip(&lengths);
+        assert_eq!(lengths, read);
+        assert!(size <= 16, "{} bytes", size);
+    }
+
+    #[test]
+    fn full_alphabet_is_small() {
+        let lengths: Vec<usize> = (0..SYMBOLS).map(|i| 7 + i % 3).collect();
+        let (size, read) = round_trip(&lengths);
+        assert_eq!(lengths, read);
+        assert!(size < 128, "{} bytes", size);
+
+        let (size, read) = round_trip(&[8; SYMBOLS]);
+        assert_eq!(vec![8; SYMBOLS], read);
+        assert!(size <= 32, "{} bytes", size);
+    }
+
+    #[test]
+    fn long_codes_are_escaped() {
+        let mut lengths = vec![0; SYMBOLS];
+        (0..40).for_each(|i| lengths[i] = i + 1);
+        lengths[40] = 40;
+
+        assert_eq!(lengths, round_trip(&lengths).1);
+    }
+
+    #[test]
+    fn lengths_longer_than_a_code() {
+        let mut lengths = vec![0; SYMBOLS];
+        lengths[0] = Code::MAX_LEN + 1;
+        let bytes = bytes_from(write(&lengths));
+
+        assert_eq!(
+            Err(Error::InvalidDictionary),
+            read(&mut BitReader::new(&bytes))
+        );
+    }
+
+    #[test]
+    fn runs_past_the_alphabet() {
+        let mut bits = Codes::new();
+        (0..ALPHABET).for_each(|s| push_bits(&mut bits, (s == LONG_ZEROS) as usize, 4));
+        // Three runs of 138 zeros.
+        (0..3).for_each(|_| push_bits(&mut bits, 127 << 1, 8));
+        let bytes = bytes_from(bits);
+
+        assert_eq!(
+            Err(Error::InvalidDictionary),
+            read(&mut BitReader::new(&bytes))
+        );
+    }
+}
@@ -1,81 +1,425 @@
 use std::collections::HashMap;
+use std::str::FromStr;
 
-use super::huffman_tree::tree;
-use crate::bytes::{bytes_from, codes_from, usize_to_bytes, usize_to_smallest_bytes, Codes};
-use crate::format::{read_dictionary, read_sizes};
+use super::huffman_tree::canonical;
+use super::huffman_tree::strategy::Strategy;
+use super::huffman_tree::tree::{self, HuffmanTree};
+use super::{ans, blocks, range_coder};
+use crate::bytes::{bytes_from, usize_to_varint, BitFormat, Codes};
+use crate::code_lengths::{self, SYMBOLS};
+use crate::error::{Error, Result};
+use crate::format::{
+    read_code_lengths, read_dictionary, read_frame, read_sizes, write_frame, Method,
+};
+use crate::histogram::{self, Histogram};
 
+pub const MODES: &[&str] = &["huffman", "ans", "range"];
+
+/// The entropy coder a file is compressed with.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Mode {
+    #[default]
+    Huffman,
+    /// Table-based asymmetric numeral systems, which spends fractions of bits on symbols.
+    Ans,
+    /// An adaptive range coder, which needs no header.
+    Range,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "huffman" => Ok(Self::Huffman),
+            "ans" => Ok(Self::Ans),
+            "range" => Ok(Self::Range),
+            _ => Err(format!("unknown mode: {}", s)),
+        }
+    }
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Huffman => "huffman",
+            Self::Ans => "ans",
+            Self::Range => "range",
+        }
+    }
+}
+
+/// Compresses the source with the mode, the strategy only matters to huffman coding.
+pub fn encode_mode(source: &[u8], mode: Mode, strategy: Strategy) -> Vec<u8> {
+    match mode {
+        Mode::Huffman => encode_with(source, strategy),
+        Mode::Ans => encode_ans(source),
+        Mode::Range => encode_range(source),
+    }
+}
+
+/// Codes the source with tANS, or stores it as-is when coding would not make it smaller. A
+/// source of a single byte value is huffman coded instead, which spends a bit on each byte.
+pub fn encode_ans(source: &[u8]) -> Vec<u8> {
+    match ans::encode(source) {
+        Some(body) if body.len() < source.len() => write_frame(Method::Ans, body, source),
+        Some(_) => write_frame(Method::Stored, source.to_vec(), source),
+        None => encode(source),
+    }
+}
+
+/// Range codes the source, or stores it as-is when coding would not make it smaller.
+pub fn encode_range(source: &[u8]) -> Vec<u8> {
+    let body = range_coder::encode(source);
+    if body.len() >= source.len() {
+        return write_frame(Method::Stored, source.to_vec(), source);
+    }
+
+    write_frame(Method::Range, body, source)
+}
+
+/// Huffman codes the source, or stores it as-is when coding would not make it smaller.
 pub fn encode(source: &[u8]) -> Vec<u8> {
-    let freq_table = freq_table(source);
-    let (tokens, hits): (Vec<u8>, Vec<usize>) = sort_map(freq_table.clone()).into_iter().unzip();
+    encode_with(source, Strategy::default())
+}
 
-    let tree = tree::with_vecdeque(&tokens, &hits, source.len());
-    let key_pairs = match tree {
-        Some(tree) => tree.stream_codes(),
-        None => Vec::new(),
-    };
+/// Like `encode`, computing the code lengths with the given strategy. The output is the same
+/// for every strategy up to ties between equally good codes.
+pub fn encode_with(source: &[u8], strategy: Strategy) -> Vec<u8> {
+    match huffman_body(source, plan(&histogram::count(source), strategy), true) {
+        Some(body) => write_frame(Method::Canonical, body, source),
+        None => write_frame(Method::Stored, source.to_vec(), source),
+    }
+}
 
-    let size_when_compressed = calculate_compression_size(freq_table, &key_pairs);
-    let buffer = swap_codes(source, key_pairs, size_when_compressed);
-    let lengths = usize_to_bytes(vec![tokens.len(), size_when_compressed]);
+/// Huffman codes the source even when that expands it, e.g. to inspect the coding.
+pub fn encode_huffman(source: &[u8]) -> Vec<u8> {
+    let plan = plan(&histogram::count(source), Strategy::default());
+    let body = huffman_body(source, plan, false).unwrap_or_default();
+    write_frame(Method::Canonical, body, source)
+}
+
+/// Like `encode_with`, building the codes from a sample of `percent` of the source rather than
+/// counting all of it. Bytes the sample missed still get a code.
+pub fn encode_sampled(source: &[u8], strategy: Strategy, percent: u8) -> Vec<u8> {
+    match huffman_body(source, sampled_plan(source, strategy, percent), true) {
+        Some(body) => write_frame(Method::Canonical, body, source),
+        None => write_frame(Method::Stored, source.to_vec(), source),
+    }
+}
+
+/// Like `encode_huffman`, building the codes from a sample.
+pub fn encode_huffman_sampled(source: &[u8], percent: u8) -> Vec<u8> {
+    let plan = sampled_plan(source, Strategy::default(), percent);
+    let body = huffman_body(source, plan, false).unwrap_or_default();
+    write_frame(Method::Canonical, body, source)
+}
+
+/// Returns None instead when `fallback` is set and the body would not be smaller than the source.
+fn huffman_body(source: &[u8], plan: Plan, fallback: bool) -> Option<Vec<u8>> {
+    if fallback && !plan.shrinks(source.len()) {
+        return None;
+    }
+
+    let header = plan.header();
+    // Sampled counts are smoothed so that every byte has a code.
+    let buffer = encode_payload(source, &plan.codes, BitFormat::HUFF)
+        .expect("every byte of the source has a code");
+
+    Some([header, buffer].concat())
+}
+
+/// What a canonical body is made of, worked out from the counts alone.
+pub(crate) struct Plan {
+    /// The code length section that follows the payload size.
+    pub code_lengths: Vec<u8>,
+    pub codes: Vec<(u8, Codes)>,
+    /// Exact for the counts the plan was made from, an estimate for any other input.
+    pub payload_bits: usize,
+}
+
+impl Plan {
+    /// The payload size in bits and the code lengths, everything before the payload.
+    pub fn header(&self) -> Vec<u8> {
+        [
+            usize_to_varint(self.payload_bits),
+            self.code_lengths.clone(),
+        ]
+        .concat()
+    }
+
+    /// Whether the body is smaller than a source of this length stored as-is.
+    pub fn shrinks(&self, length: usize) -> bool {
+        usize_to_varint(self.payload_bits).len()
+            + self.code_lengths.len()
+            + self.payload_bits.div_ceil(8)
+            < length
+    }
+}
+
+pub(crate) fn plan(histogram: &Histogram, strategy: Strategy) -> Plan {
+    let (tokens, hits) = dictionary(histogram);
+    let codes = canonical_codes(&tokens, &hits, strategy);
+
+    let mut lengths = vec![0; SYMBOLS];
+    for (t, code) in &codes {
+        lengths[*t as usize] = code.len();
+    }
+
+    Plan {
+        code_lengths: bytes_from(code_lengths::write(&lengths)),
+        payload_bits: calculate_compression_size(histogram, &codes),
+        codes,
+    }
+}
+
+/// A plan from a sample of the source, with the payload size of all of it.
+fn sampled_plan(source: &[u8], strategy: Strategy, percent: u8) -> Plan {
+    let mut plan = plan(&histogram::sample(source, percent), strategy);
+
+    let mut lengths = [0; 256];
+    for (t, code) in &plan.codes {
+        lengths[*t as usize] = code.len();
+    }
+    plan.payload_bits = source.iter().map(|t| lengths[*t as usize]).sum();
+
+    plan
+}
+
+/// Writes the dictionary of counts files had before canonical codes, to test reading them.
+#[cfg(test)]
+pub(crate) fn encode_counts(source: &[u8]) -> Vec<u8> {
+    use crate::bytes::{usize_to_bytes, usize_to_smallest_bytes};
+
+    let histogram = histogram::count(source);
+    let (tokens, hits) = dictionary(&histogram);
+    let key_pairs: Vec<(u8, Codes)> = tree::with_vecdeque(&tokens, &hits, source.len())
+        .map(|tree| tree.stream_codes())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(t, code)| (t, code.to_codes()))
+        .collect();
+
+    let size_when_compressed = calculate_compression_size(&histogram, &key_pairs);
     let (byte_size, hits_as_bytes) = usize_to_smallest_bytes(hits);
+    let buffer = encode_payload(source, &key_pairs, BitFormat::HUFF)
+        .expect("the codes are built from the source's own counts");
+    let lengths = usize_to_bytes(vec![tokens.len(), size_when_compressed]);
 
-    vec![lengths, vec![byte_size], tokens, hits_as_bytes, buffer].concat()
+    let body = [lengths, vec![byte_size], tokens, hits_as_bytes, buffer].concat();
+    write_frame(Method::Huffman, body, source)
 }
 
-pub fn decode(source: &[u8]) -> Vec<u8> {
-    let (tokens, hits) = read_dictionary(source);
-    let (dictionary_size, size_when_compressed, remaining_source) = read_sizes(source);
+/// Canonical codes with the lengths the strategy computes from the counts.
+fn canonical_codes(tokens: &[u8], hits: &[usize], strategy: Strategy) -> Vec<(u8, Codes)> {
+    let lengths = strategy.lengths(tokens, hits);
+
+    // The lengths of a huffman code always fit the code space.
+    canonical::codes(&lengths).unwrap_or_default()
+}
+
+pub fn decode(source: &[u8]) -> Result<Vec<u8>> {
+    let frame = read_frame(source)?;
+    let output = match frame.method {
+        Method::Huffman => decode_huffman(source)?,
+        Method::Canonical => decode_canonical(source)?,
+        Method::Ans => ans::decode(frame.body)?,
+        Method::Range => {
+            // Only frames with a method byte can be range coded, and they have a trailer.
+            let length = frame.trailer.as_ref().ok_or(Error::Truncated)?.length;
+            range_coder::decode(frame.body, length)?
+        }
+        Method::Stored => frame.body.to_vec(),
+        Method::Blocks => blocks::decode(frame.body)?,
+    };
+
+    if let Some(trailer) = frame.trailer {
+        trailer.verify(&output)?;
+    }
+
+    Ok(output)
+}
+
+fn decode_huffman(source: &[u8]) -> Result<Vec<u8>> {
+    let (tokens, hits) = read_dictionary(source)?;
 
     let size = hits.iter().sum::<usize>();
     let tree = tree::with_vecdeque(&tokens, &hits, size);
-    let compressed_source = &remaining_source[dictionary_size..];
-    let codes = codes_from(compressed_source, size_when_compressed);
+    let codes = read_payload(source)?;
 
-    match tree {
+    let output = match tree {
         Some(tree) => tree.read(codes),
         None => Vec::new(),
+    };
+
+    if output.len() != size {
+        return Err(Error::LengthMismatch {
+            expected: size,
+            actual: output.len(),
+        });
     }
+
+    Ok(output)
 }
 
-fn freq_table(data: &[u8]) -> HashMap<u8, usize> {
-    let mut map = HashMap::with_capacity(data.len());
+fn decode_canonical(source: &[u8]) -> Result<Vec<u8>> {
+    let (tokens, codes) = read_codes(source)?;
+    let codes_read = read_payload(source)?;
 
-    for token in data {
-        let count = map.entry(*token).or_insert(0);
-        *count += 1;
-    }
+    let capacity = match read_frame(source)?.trailer {
+        Some(trailer) => trailer.length.min(codes_read.len()),
+        None => 0,
+    };
+    let tree = tree::with_codes(&tokens, &codes, &vec![0; tokens.len()], capacity);
+
+    Ok(match tree {
+        Some(tree) => tree.read(codes_read),
+        None => Vec::new(),
+    })
+}
+
+/// The tokens of a canonical file and their codes, rebuilt from the code lengths.
+fn read_codes(source: &[u8]) -> Result<(Vec<u8>, Vec<Codes>)> {
+    let lengths: Vec<(u8, usize)> = read_code_lengths(source)?
+        .into_iter()
+        .enumerate()
+        .map(|(t, length)| (t as u8, length))
+        .collect();
 
-    map.shrink_to_fit();
-    map
+    Ok(canonical::codes(&lengths)
+        .ok_or(Error::InvalidDictionary)?
+        .into_iter()
+        .unzip())
 }
 
-fn sort_map(map: HashMap<u8, usize>) -> Vec<(u8, usize)> {
-    let mut table: Vec<(u8, usize)> = map.into_iter().collect();
+/// Builds the tree `encode` would use for the source.
+pub fn build_tree(source: &[u8]) -> Option<HuffmanTree<u8>> {
+    let histogram = histogram::count(source);
+    let (tokens, hits) = dictionary(&histogram);
+    let (tokens, codes): (Vec<u8>, Vec<Codes>) =
+        canonical_codes(&tokens, &hits, Strategy::default())
+            .into_iter()
+            .unzip();
+    let hits: Vec<usize> = tokens
+        .iter()
+        .map(|t| histogram[*t as usize] as usize)
+        .collect();
+
+    tree::with_codes(&tokens, &codes, &hits, source.len())
+}
+
+/// The tokens of the source and their counts, in the order the tree is built from.
+pub fn source_dictionary(source: &[u8]) -> (Vec<u8>, Vec<usize>) {
+    dictionary(&histogram::count(source))
+}
+
+/// The bytes that occur, sorted by count and then by descending value, with their counts.
+fn dictionary(histogram: &Histogram) -> (Vec<u8>, Vec<usize>) {
+    let mut table: Vec<(u8, usize)> = (0..=u8::MAX)
+        .zip(histogram.iter())
+        .filter(|(_, count)| **count > 0)
+        .map(|(t, count)| (t, *count as usize))
+        .collect();
     table.sort_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)));
 
-    table
+    table.into_iter().unzip()
+}
+
+/// The tokens of an encoded file and their counts. Canonical files store code lengths instead of
+/// counts, so they are decoded in full to count them; callers that decode the file anyway should
+/// count its data with `source_dictionary` instead.
+pub fn read_counts(source: &[u8]) -> Result<(Vec<u8>, Vec<usize>)> {
+    match read_frame(source)?.method {
+        Method::Huffman => read_dictionary(source),
+        Method::Canonical => Ok(source_dictionary(&decode(source)?)),
+        Method::Stored | Method::Ans | Method::Range | Method::Blocks => Err(Error::NotHuffman),
+    }
+}
+
+/// Rebuilds the tree from the header of an encoded file, with the counts on its nodes. Decodes
+/// canonical files, see `read_counts`.
+pub fn read_tree(source: &[u8]) -> Result<HuffmanTree<u8>> {
+    read_tree_counted(source, read_counts(source)?)
 }
 
-fn swap_codes(source: &[u8], key_pairs: Vec<(u8, Codes)>, size: usize) -> Vec<u8> {
-    let key_map: HashMap<u8, Codes> = key_pairs.into_iter().collect();
-    let mut buffer = Vec::with_capacity(size);
+/// Rebuilds the tree from the header of an encoded file, with the given counts on its nodes.
+pub fn read_tree_counted(
+    source: &[u8],
+    (tokens, hits): (Vec<u8>, Vec<usize>),
+) -> Result<HuffmanTree<u8>> {
+    let size = hits.iter().sum::<usize>();
+
+    if read_frame(source)?.method == Method::Huffman {
+        return tree::with_vecdeque(&tokens, &hits, size).ok_or(Error::InvalidDictionary);
+    }
+
+    let (code_tokens, codes) = read_codes(source)?;
+    let hits_map: HashMap<u8, usize> = tokens.into_iter().zip(hits).collect();
+    let hits: Vec<usize> = code_tokens
+        .iter()
+        .map(|t| hits_map.get(t).copied().unwrap_or_default())
+        .collect();
+    tree::with_codes(&code_tokens, &codes, &hits, size).ok_or(Error::InvalidDictionary)
+}
+
+/// The payload bits of an encoded file, without the header.
+pub fn read_payload(source: &[u8]) -> Result<Codes> {
+    let (dictionary_size, size_when_compressed, remaining_source) = read_sizes(source)?;
+    let compressed_source = &remaining_source[dictionary_size..];
+
+    Ok(BitFormat::HUFF.unpack(compressed_source, size_when_compressed))
+}
 
+/// The codes of the source packed in `format`, without a header or frame, e.g. the payload of a
+/// format that stores its code lengths itself, like DEFLATE or JPEG.
+pub fn encode_payload(source: &[u8], codes: &[(u8, Codes)], format: BitFormat) -> Result<Vec<u8>> {
+    let mut table: Vec<&[bool]> = vec![&[]; 256];
+    for (t, code) in codes {
+        table[*t as usize] = code;
+    }
+
+    let size = source.iter().map(|t| table[*t as usize].len()).sum();
+    let mut buffer = Codes::with_capacity(size);
     for t in source {
-        if let Some(code) = key_map.get(t) {
-            code.iter().for_each(|bit| buffer.push(*bit));
+        let code = table[*t as usize];
+        if code.is_empty() {
+            return Err(Error::NoCode(*t));
         }
+        buffer.extend_from_slice(code);
+    }
+
+    Ok(format.pack(&buffer))
+}
+
+/// Decodes the first `length` bytes of a payload packed in `format` with the given codes.
+pub fn decode_payload(
+    payload: &[u8],
+    codes: &[(u8, Codes)],
+    length: usize,
+    format: BitFormat,
+) -> Result<Vec<u8>> {
+    let (tokens, codes): (Vec<u8>, Vec<Codes>) = codes.iter().cloned().unzip();
+    let bits = format.unpack(payload, payload.len() * 8);
+    let tree = tree::with_codes(&tokens, &codes, &vec![0; tokens.len()], length)
+        .ok_or(Error::InvalidDictionary)?;
+
+    // Padding may decode to a few more bytes.
+    let mut output = tree.read(bits);
+    if output.len() < length {
+        return Err(Error::LengthMismatch {
+            expected: length,
+            actual: output.len(),
+        });
     }
+    output.truncate(length);
 
-    bytes_from(buffer)
+    Ok(output)
 }
 
-fn calculate_compression_size(
-    freq_table: HashMap<u8, usize>,
-    key_pairs: &[(u8, Vec<bool>)],
-) -> usize {
+fn calculate_compression_size(histogram: &Histogram, key_pairs: &[(u8, Vec<bool>)]) -> usize {
     key_pairs
         .iter()
-        .filter_map(|(k, codes)| freq_table.get(k).map(|count| count * codes.len()))
+        .map(|(k, codes)| histogram[*k as usize] as usize * codes.len())
         .sum::<usize>()
 }
 
@@ -87,7 +431,7 @@ mod test {
     fn abcde() {
         let input = b"aaaaaabccccccddeeeee";
         let codes = encode(input);
-        let decode = decode(&codes);
+        let decode = decode(&codes).unwrap();
 
         assert_eq!(&input[..], decode);
     }
@@ -96,8 +440,170 @@ mod test {
     fn geeksforgeeks() {
         let input = b"geeksforgeeks";
         let codes = encode(input);
-        let decode = decode(&codes);
+        let decode = decode(&codes).unwrap();
 
         assert_eq!(&input[..], decode);
     }
+
+    #[test]
+    fn empty() {
+        assert_eq!(Vec::<u8>::new(), decode(&encode(b"")).unwrap());
+    }
+
+    #[test]
+    fn corrupt_input_does_not_panic() {
+        let input = b"There and back again. A hobbits tale, by Bilbo Baggins";
+        let codes = encode_huffman(input);
+
+        for i in 0..codes.len() {
+            for flip in &[0x01, 0x80, 0xff] {
+                let mut corrupt = codes.clone();
+                corrupt[i] ^= flip;
+                let _ = decode(&corrupt);
+            }
+            assert!(decode(&codes[..i]).is_err());
+        }
+    }
+
+    #[test]
+    fn stored_when_coding_expands() {
+        let input: Vec<u8> = (0..=255).collect();
+        let codes = encode(&input);
+
+        assert_eq!(Method::Stored, read_frame(&codes).unwrap().method);
+        assert_eq!(input.len() + 17, codes.len());
+        assert_eq!(input, decode(&codes).unwrap());
+    }
+
+    #[test]
+    fn huffman_when_coding_shrinks() {
+        let input = [b'a'; 100];
+        let codes = encode(&input);
+
+        assert_eq!(Method::Canonical, read_frame(&codes).unwrap().method);
+        assert!(codes.len() < input.len());
+    }
+
+    #[test]
+    fn ans_round_trip() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 89 / 30) as u8).collect();
+        let codes = encode_mode(&input, Mode::Ans, Strategy::default());
+
+        assert_eq!(Method::Ans, read_frame(&codes).unwrap().method);
+        assert_eq!(input, decode(&codes).unwrap());
+        assert_eq!(Err(Error::NotHuffman), read_tree(&codes).map(|_| ()));
+
+        let stored = encode_ans(b"abc");
+        assert_eq!(Method::Stored, read_frame(&stored).unwrap().method);
+    }
+
+    #[test]
+    fn range_round_trip() {
+        let input = b"abracadabra abracadabra abracadabra abracadabra";
+        let codes = encode_mode(input, Mode::Range, Strategy::default());
+
+        assert_eq!(Method::Range, read_frame(&codes).unwrap().method);
+        assert_eq!(&input[..], decode(&codes).unwrap());
+        assert!(decode(&codes[..codes.len() - 13]).is_err());
+    }
+
+    #[test]
+    fn payload_formats() {
+        use crate::bytes::{BitOrder, Polarity};
+
+        // The canonical codes of lengths 1, 2 and 2.
+        let codes = vec![
+            (b'a', vec![false]),
+            (b'b', vec![true, false]),
+            (b'c', vec![true, true]),
+        ];
+        let jpeg = BitFormat {
+            order: BitOrder::MsbFirst,
+            polarity: Polarity::LeftIsOne,
+        };
+
+        assert_eq!(Ok(vec![0b0101_1000]), encode_payload(b"abc", &codes, jpeg));
+        assert_eq!(
+            Ok(vec![0b0001_1010]),
+            encode_payload(b"abc", &codes, BitFormat::HUFF)
+        );
+        for format in [jpeg, BitFormat::HUFF] {
+            let payload = encode_payload(b"abcab", &codes, format).unwrap();
+            assert_eq!(
+                b"abcab".to_vec(),
+                decode_payload(&payload, &codes, 5, format).unwrap()
+            );
+        }
+        assert!(decode_payload(&[0b0101_1000], &codes, 9, jpeg).is_err());
+        assert_eq!(
+            Err(Error::NoCode(b'd')),
+            encode_payload(b"abcd", &codes, jpeg)
+        );
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let mut codes = encode_huffman(b"geeksforgeeks");
+        let checksum = codes.len() - 12;
+        codes[checksum] ^= 0xff;
+
+        assert!(matches!(
+            decode(&codes),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn full_alphabet_header_is_compact() {
+        let input: Vec<u8> = (0..8192)
+            .map(|i| {
+                if i % 2 == 0 {
+                    b'e'
+                } else {
+                    (i / 2 % 256) as u8
+                }
+            })
+            .collect();
+        let (dictionary_size, _, _) = read_sizes(&encode_huffman(&input)).unwrap();
+        let (counts_size, _, _) = read_sizes(&encode_counts(&input)).unwrap();
+
+        assert!(
+            dictionary_size * 4 < counts_size,
+            "{} bytes",
+            dictionary_size
+        );
+        assert!(dictionary_size < 64, "{} bytes", dictionary_size);
+    }
+
+    #[test]
+    fn strategies_round_trip() {
+        let input = b"There and back again. A hobbits tale, by Bilbo Baggins";
+
+        for strategy in [Strategy::TwoQueue, Strategy::Heap, Strategy::Moffat] {
+            let codes = encode_with(input, strategy);
+            assert_eq!(&input[..], decode(&codes).unwrap());
+            assert_eq!(encode(input).len(), codes.len());
+        }
+    }
+
+    #[test]
+    fn legacy_counts() {
+        let input = b"geeksforgeeks";
+        let codes = encode_counts(input);
+
+        assert_eq!(&input[..], decode(&codes).unwrap());
+        assert_eq!(
+            read_counts(&encode_huffman(input)).unwrap(),
+            read_counts(&codes).unwrap()
+        );
+    }
+
+    #[test]
+    fn legacy_unframed() {
+        let input = b"geeksforgeeks";
+        let codes = encode_counts(input);
+        let body = &codes[5..codes.len() - 12];
+
+        assert_eq!(&input[..], decode(body).unwrap());
+    }
 }
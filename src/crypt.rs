@@ -0,0 +1,241 @@
+//! Compressed files encrypted with a passphrase. A key is derived from the passphrase and a
+//! random salt with Argon2id, and the compressed file is sealed with ChaCha20-Poly1305 under a
+//! random nonce. The header is authenticated along with the ciphertext:
+//!
+//! ```text
+//! MAGIC | version | key derivation | memory KiB, passes, lanes | salt | nonce | ciphertext
+//! ```
+//!
+//! The costs are varints and the ciphertext ends with the Poly1305 tag. The trailer of the
+//! compressed file is inside the ciphertext, so nothing about the original but its approximate
+//! size is readable without the passphrase.
+
+use std::convert::TryFrom;
+
+use argon2::{Algorithm, Argon2, Params as Argon2Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use crate::bytes::{read_be_u8, read_varint, usize_to_varint};
+use crate::error::{Error, Result};
+
+/// Starts with a byte that is neither `format::MAGIC` nor text, so an encrypted file cannot
+/// be read as a frame or as armor.
+pub const MAGIC: &[u8] = b"\x89HUFENC";
+const VERSION: u8 = 1;
+/// The only key derivation so far.
+const ARGON2ID: u8 = 1;
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const KEY_SIZE: usize = 32;
+
+/// Costs of the key derivation, stored in the header so they can be raised later.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Params {
+    /// Memory in KiB.
+    pub memory: u32,
+    pub passes: u32,
+    pub lanes: u32,
+}
+
+impl Params {
+    /// The Argon2 defaults, 19 MiB and two passes.
+    pub const DEFAULT: Self = Self {
+        memory: Argon2Params::DEFAULT_M_COST,
+        passes: Argon2Params::DEFAULT_T_COST,
+        lanes: Argon2Params::DEFAULT_P_COST,
+    };
+    /// A little above `DEFAULT`, the costs this version writes. The header is read before the
+    /// passphrase is checked, so a forged one must not make decryption take much more memory.
+    const MAX: Self = Self {
+        memory: 1 << 15,
+        passes: 4,
+        lanes: 4,
+    };
+
+    fn key(self, passphrase: &[u8], salt: &[u8]) -> Result<[u8; KEY_SIZE]> {
+        if self.memory > Self::MAX.memory
+            || self.passes > Self::MAX.passes
+            || self.lanes > Self::MAX.lanes
+        {
+            return Err(Error::InvalidKeyDerivation);
+        }
+
+        let params = Argon2Params::new(self.memory, self.passes, self.lanes, Some(KEY_SIZE))
+            .map_err(|_| Error::InvalidKeyDerivation)?;
+        let mut key = [0; KEY_SIZE];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase, salt, &mut key)
+            .map_err(|_| Error::InvalidKeyDerivation)?;
+
+        Ok(key)
+    }
+}
+
+pub fn is_encrypted(source: &[u8]) -> bool {
+    source.starts_with(MAGIC)
+}
+
+pub fn encrypt(data: &[u8], passphrase: &[u8]) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_SIZE];
+    let mut nonce = [0; NONCE_SIZE];
+    getrandom::getrandom(&mut salt).map_err(|_| Error::NoRandomness)?;
+    getrandom::getrandom(&mut nonce).map_err(|_| Error::NoRandomness)?;
+
+    encrypt_with(data, passphrase, Params::DEFAULT, salt, nonce)
+}
+
+fn encrypt_with(
+    data: &[u8],
+    passphrase: &[u8],
+    params: Params,
+    salt: [u8; SALT_SIZE],
+    nonce: [u8; NONCE_SIZE],
+) -> Result<Vec<u8>> {
+    let mut header = [MAGIC, &[VERSION, ARGON2ID]].concat();
+    for cost in [params.memory, params.passes, params.lanes].iter() {
+        header.extend(usize_to_varint(*cost as usize));
+    }
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    let key = params.key(passphrase, &salt)?;
+    let payload = Payload {
+        msg: data,
+        aad: &header,
+    };
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key))
+        .encrypt(Nonce::from_slice(&nonce), payload)
+        .expect("a byte slice fits the cipher's length limit");
+
+    Ok([header, ciphertext].concat())
+}
+
+/// The compressed file, once the passphrase and the whole file have been authenticated.
+pub fn decrypt(source: &[u8], passphrase: &[u8]) -> Result<Vec<u8>> {
+    let remaining = source.strip_prefix(MAGIC).ok_or(Error::NotEncrypted)?;
+    let (version, remaining) = read_be_u8(remaining).ok_or(Error::Truncated)?;
+    if version as u8 != VERSION {
+        return Err(Error::UnsupportedVersion(version as u8));
+    }
+    let (derivation, remaining) = read_be_u8(remaining).ok_or(Error::Truncated)?;
+    if derivation as u8 != ARGON2ID {
+        return Err(Error::InvalidKeyDerivation);
+    }
+
+    let mut costs = [0u32; 3];
+    let mut remaining = remaining;
+    for cost in costs.iter_mut() {
+        let (value, rest) = read_varint(remaining).ok_or(Error::Truncated)?;
+        *cost = u32::try_from(value).map_err(|_| Error::InvalidKeyDerivation)?;
+        remaining = rest;
+    }
+    let params = Params {
+        memory: costs[0],
+        passes: costs[1],
+        lanes: costs[2],
+    };
+
+    if remaining.len() < SALT_SIZE + NONCE_SIZE {
+        return Err(Error::Truncated);
+    }
+    let (salt, remaining) = remaining.split_at(SALT_SIZE);
+    let (nonce, ciphertext) = remaining.split_at(NONCE_SIZE);
+    let header = &source[..source.len() - ciphertext.len()];
+
+    let key = params.key(passphrase, salt)?;
+    let payload = Payload {
+        msg: ciphertext,
+        aad: header,
+    };
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .map_err(|_| Error::DecryptionFailed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Cheap enough to run in debug builds.
+    const FAST: Params = Params {
+        memory: 64,
+        passes: 1,
+        lanes: 1,
+    };
+
+    fn encrypted(data: &[u8], passphrase: &[u8]) -> Vec<u8> {
+        encrypt_with(data, passphrase, FAST, [7; SALT_SIZE], [9; NONCE_SIZE]).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = crate::compress::encode_huffman(b"There and back again");
+        let source = encrypted(&data, b"correct horse");
+
+        assert!(is_encrypted(&source));
+        assert!(!is_encrypted(&data));
+        assert_eq!(Ok(data), decrypt(&source, b"correct horse"));
+        assert_eq!(Ok(Vec::new()), decrypt(&encrypted(b"", b"pass"), b"pass"));
+    }
+
+    #[test]
+    fn salt_and_nonce_are_random() {
+        let data = b"the same file twice";
+        let passphrase = b"correct horse";
+        assert_ne!(
+            encrypt(data, passphrase).unwrap(),
+            encrypt(data, passphrase).unwrap()
+        );
+    }
+
+    #[test]
+    fn wrong_passphrase_fails() {
+        let source = encrypted(b"secret", b"correct horse");
+        assert_eq!(
+            Err(Error::DecryptionFailed),
+            decrypt(&source, b"battery staple")
+        );
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let source = encrypted(b"secret", b"correct horse");
+
+        // Any byte past the version and key derivation: costs, salt, nonce, ciphertext or tag.
+        for i in MAGIC.len() + 2..source.len() {
+            let mut tampered = source.clone();
+            tampered[i] ^= 1;
+            assert!(decrypt(&tampered, b"correct horse").is_err(), "byte {}", i);
+        }
+        for len in 0..source.len() {
+            assert!(
+                decrypt(&source[..len], b"correct horse").is_err(),
+                "{}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn costly_headers_are_rejected() {
+        // The memory cost of `FAST` takes one byte. 2 GiB would still be a valid Argon2 cost.
+        let source = encrypted(b"secret", b"pass");
+        for memory in [1 << 21, u32::MAX] {
+            let forged = [
+                &source[..MAGIC.len() + 2],
+                &usize_to_varint(memory as usize),
+                &source[MAGIC.len() + 3..],
+            ]
+            .concat();
+
+            assert_eq!(Err(Error::InvalidKeyDerivation), decrypt(&forged, b"pass"));
+        }
+    }
+
+    #[test]
+    fn default_costs_are_within_bounds() {
+        let source = encrypt(b"secret", b"pass").unwrap();
+        assert_eq!(Ok(b"secret".to_vec()), decrypt(&source, b"pass"));
+    }
+}
@@ -0,0 +1,337 @@
+//! Compares the dictionaries of two compressed files, e.g. to spot drift between exports of the
+//! same kind of data. Symbols are aligned by byte value. The cross-entropy is what A's data
+//! would cost coded with B's code, against its own code.
+//!
+//! Counts come from the header of huffman files and from decoding any other file. Files coded
+//! without huffman codes are compared with the code huffman coding would give their data.
+
+use serde::Serialize;
+
+use crate::compress::{build_tree, decode, read_tree_counted, source_dictionary};
+use crate::error::Result;
+use crate::format::{read_code_lengths, read_dictionary, read_frame, Method};
+use crate::stats::{character, Format};
+
+use prettytable::Table;
+
+#[derive(Debug, Serialize)]
+pub struct Diff {
+    pub size_a: usize,
+    pub size_b: usize,
+    /// Order-0 entropy of A in bits per symbol.
+    pub entropy_a: f64,
+    /// Average code length of A's data with A's code.
+    pub code_length_a: f64,
+    /// Average code length of A's data with B's code, or `None` when A has bytes B's code
+    /// cannot code.
+    pub cross_entropy: Option<f64>,
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    /// Every byte in either file, the largest change in share first.
+    pub symbols: Vec<SymbolDiff>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SymbolDiff {
+    pub byte: u8,
+    pub character: String,
+    pub count_a: usize,
+    pub count_b: usize,
+    pub count_delta: i64,
+    /// The change in the byte's share of the data, in percentage points.
+    pub share_delta: f64,
+    pub bits_a: Option<usize>,
+    pub bits_b: Option<usize>,
+}
+
+/// The counts and code lengths of every byte value of one file.
+struct Side {
+    counts: [usize; 256],
+    lengths: [Option<usize>; 256],
+    size: usize,
+}
+
+impl Side {
+    /// Huffman files keep their counts in the header. Other files are decoded once and their
+    /// bytes counted: canonical files take their code lengths straight from the header, files
+    /// without huffman codes the lengths huffman coding would give their data.
+    fn read(source: &[u8]) -> Result<Self> {
+        let mut lengths = [None; 256];
+        let (tokens, hits) = match read_frame(source)?.method {
+            Method::Huffman => {
+                let dictionary = read_dictionary(source)?;
+                for (t, code) in read_tree_counted(source, dictionary.clone())?.stream_codes() {
+                    lengths[t as usize] = Some(code.len);
+                }
+                dictionary
+            }
+            Method::Canonical => {
+                for (t, length) in read_code_lengths(source)?.into_iter().enumerate() {
+                    lengths[t] = Some(length).filter(|length| *length > 0);
+                }
+                source_dictionary(&decode(source)?)
+            }
+            Method::Stored | Method::Ans | Method::Range | Method::Blocks => {
+                let data = decode(source)?;
+                for (t, code) in build_tree(&data)
+                    .iter()
+                    .flat_map(|tree| tree.stream_codes())
+                {
+                    lengths[t as usize] = Some(code.len);
+                }
+                source_dictionary(&data)
+            }
+        };
+
+        let mut counts = [0; 256];
+        for (t, hit) in tokens.into_iter().zip(hits) {
+            counts[t as usize] = hit;
+        }
+
+        Ok(Self {
+            counts,
+            lengths,
+            size: counts.iter().sum(),
+        })
+    }
+
+    fn share(&self, t: usize) -> f64 {
+        if self.size == 0 {
+            return 0.0;
+        }
+
+        self.counts[t] as f64 / self.size as f64
+    }
+
+    /// The average bits per symbol of this side's data with the code lengths given, `None`
+    /// when some byte that occurs has no code.
+    fn code_length(&self, lengths: &[Option<usize>; 256]) -> Option<f64> {
+        (0..256)
+            .filter(|t| self.counts[*t] > 0)
+            .map(|t| lengths[t].map(|bits| self.share(t) * bits as f64))
+            .sum()
+    }
+}
+
+pub fn diff(a: &[u8], b: &[u8]) -> Result<Diff> {
+    let a = Side::read(a)?;
+    let b = Side::read(b)?;
+
+    let mut symbols: Vec<SymbolDiff> = (0..256)
+        .filter(|t| a.counts[*t] > 0 || b.counts[*t] > 0)
+        .map(|t| SymbolDiff {
+            byte: t as u8,
+            character: character(t as u8),
+            count_a: a.counts[t],
+            count_b: b.counts[t],
+            count_delta: b.counts[t] as i64 - a.counts[t] as i64,
+            share_delta: (b.share(t) - a.share(t)) * 100.0,
+            bits_a: a.lengths[t],
+            bits_b: b.lengths[t],
+        })
+        .collect();
+    symbols.sort_by(|x, y| {
+        y.share_delta
+            .abs()
+            .total_cmp(&x.share_delta.abs())
+            .then(x.byte.cmp(&y.byte))
+    });
+
+    let only = |symbols: &[SymbolDiff], f: fn(&SymbolDiff) -> bool| {
+        let mut only: Vec<&SymbolDiff> = symbols.iter().filter(|s| f(s)).collect();
+        only.sort_by_key(|s| s.byte);
+        only.into_iter().map(|s| s.character.clone()).collect()
+    };
+
+    Ok(Diff {
+        size_a: a.size,
+        size_b: b.size,
+        entropy_a: (0..256)
+            .map(|t| a.share(t))
+            .filter(|p| *p > 0.0)
+            .fold(0.0, |entropy: f64, p| entropy + p * -p.log2())
+            .max(0.0),
+        code_length_a: a.code_length(&a.lengths).unwrap_or_default(),
+        cross_entropy: a.code_length(&b.lengths),
+        only_in_a: only(&symbols, |s| s.count_b == 0),
+        only_in_b: only(&symbols, |s| s.count_a == 0),
+        symbols,
+    })
+}
+
+pub fn print(a: &[u8], b: &[u8], format: Format) -> Result<()> {
+    let diff = diff(a, b)?;
+    match format {
+        Format::Table => print_table(&diff),
+        Format::Json => println!("{}", to_json(&diff)),
+        Format::Csv => print!("{}", to_csv(&diff)),
+    }
+    Ok(())
+}
+
+fn to_json(diff: &Diff) -> String {
+    // Plain structs of numbers and strings always serialise.
+    serde_json::to_string_pretty(diff).unwrap_or_default()
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn to_csv(diff: &Diff) -> String {
+    let mut csv = String::from("metric,value\n");
+    csv += &format!("size_a,{}\n", diff.size_a);
+    csv += &format!("size_b,{}\n", diff.size_b);
+    csv += &format!("entropy_a,{:.6}\n", diff.entropy_a);
+    csv += &format!("code_length_a,{:.6}\n", diff.code_length_a);
+    csv += &format!(
+        "cross_entropy,{}\n",
+        optional(diff.cross_entropy.map(|bits| format!("{:.6}", bits)))
+    );
+
+    csv += "\nbyte,character,count_a,count_b,count_delta,share_delta,bits_a,bits_b\n";
+    for symbol in &diff.symbols {
+        csv += &format!(
+            "{},\"{}\",{},{},{},{:.6},{},{}\n",
+            symbol.byte,
+            symbol.character.replace('"', "\"\""),
+            symbol.count_a,
+            symbol.count_b,
+            symbol.count_delta,
+            symbol.share_delta,
+            optional(symbol.bits_a),
+            optional(symbol.bits_b)
+        );
+    }
+
+    csv
+}
+
+fn print_table(diff: &Diff) {
+    println!("Symbols:\t{} in A, {} in B", diff.size_a, diff.size_b);
+    println!("Entropy of A:\t{:.4} bits/symbol", diff.entropy_a);
+    println!("A with A's code:\t{:.4} bits/symbol", diff.code_length_a);
+    match diff.cross_entropy {
+        Some(bits) => println!(
+            "A with B's code:\t{:.4} bits/symbol ({:+.4})",
+            bits,
+            bits - diff.code_length_a
+        ),
+        None => println!("A with B's code:\tnot codable, B has no code for some bytes of A"),
+    }
+    let quoted = |characters: &[String]| {
+        characters
+            .iter()
+            .map(|c| format!("\"{}\"", c))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    println!("Only in A:\t{}", quoted(&diff.only_in_a));
+    println!("Only in B:\t{}", quoted(&diff.only_in_b));
+
+    let mut table = Table::new();
+    table.add_row(row![
+        "Character",
+        "Count A",
+        "Count B",
+        "Delta",
+        "Share",
+        "Bits A",
+        "Bits B"
+    ]);
+    for symbol in &diff.symbols {
+        table.add_row(row![
+            format!("\"{}\"", symbol.character),
+            symbol.count_a,
+            symbol.count_b,
+            format!("{:+}", symbol.count_delta),
+            format!("{:+.2}", symbol.share_delta),
+            optional(symbol.bits_a),
+            optional(symbol.bits_b),
+        ]);
+    }
+
+    println!("Symbols by change in share, in percentage points:");
+    table.printstd();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compress::{encode_huffman as encode, encode_mode, Mode};
+    use crate::huffman_tree::strategy::Strategy;
+
+    #[test]
+    fn identical_files() {
+        let a = encode(b"aaaaaaaaabbbbbbbbb\n");
+        let diff = diff(&a, &a).unwrap();
+
+        assert_eq!(Some(diff.code_length_a), diff.cross_entropy);
+        assert!(diff.symbols.iter().all(|s| s.count_delta == 0));
+        assert!(diff.only_in_a.is_empty() && diff.only_in_b.is_empty());
+        assert!(diff
+            .symbols
+            .iter()
+            .all(|s| s.bits_a.is_some() && s.bits_a == s.bits_b));
+    }
+
+    #[test]
+    fn drift() {
+        let a = encode(b"aaaaaaaabbbbcc\n");
+        let b = encode(b"aaaabbbbbbbbdd");
+        let diff = diff(&a, &b).unwrap();
+
+        assert_eq!(vec!["\\n", "c"], diff.only_in_a);
+        assert_eq!(vec!["d"], diff.only_in_b);
+        assert_eq!(None, diff.cross_entropy);
+
+        let a_symbol = diff.symbols.iter().find(|s| s.byte == b'a').unwrap();
+        assert_eq!(
+            (8, 4, -4),
+            (a_symbol.count_a, a_symbol.count_b, a_symbol.count_delta)
+        );
+        assert_eq!(b'b', diff.symbols[0].byte);
+    }
+
+    #[test]
+    fn cross_entropy_costs_more() {
+        let a = encode(b"aaaaaaaabbbbccd");
+        let b = encode(b"abcddddddddcccc");
+        let diff = diff(&a, &b).unwrap();
+
+        // A's own code: a 1 bit, b 2, c and d 3, so 25 bits for 15 bytes.
+        assert_eq!(25.0 / 15.0, diff.code_length_a);
+        assert!(diff.cross_entropy.unwrap() > diff.code_length_a);
+        assert!(diff.entropy_a <= diff.code_length_a);
+    }
+
+    #[test]
+    fn files_without_huffman_codes() {
+        let source = b"There and back again, a hobbit's tale";
+        let ans = encode_mode(source, Mode::Ans, Strategy::default());
+        let diff = diff(&ans, &encode(source)).unwrap();
+
+        assert_eq!(source.len(), diff.size_a);
+        assert_eq!(Some(diff.code_length_a), diff.cross_entropy);
+    }
+
+    #[test]
+    fn single_symbol_entropy() {
+        let a = encode(b"aaaa");
+        let diff = diff(&a, &a).unwrap();
+
+        assert_eq!("0.0000", format!("{:.4}", diff.entropy_a));
+    }
+
+    #[test]
+    fn outputs() {
+        let diff = diff(&encode(b"\"ab"), &encode(b"abb")).unwrap();
+
+        let csv = to_csv(&diff);
+        assert!(csv.contains("\ncross_entropy,\n"));
+        assert!(csv.contains("\n34,\"\\\"\"\",1,0,-1,"));
+        let json: serde_json::Value = serde_json::from_str(&to_json(&diff)).unwrap();
+        assert_eq!(serde_json::Value::Null, json["cross_entropy"]);
+        assert_eq!(3, json["symbols"].as_array().unwrap().len());
+    }
+}
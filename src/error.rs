@@ -0,0 +1,78 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The input ended before the header or payload was complete.
+    Truncated,
+    /// The header declares an integer width other than 8, 16, 32 or 64 bits.
+    InvalidByteSize(usize),
+    /// The dictionary cannot describe a byte alphabet or disagrees with the payload.
+    InvalidDictionary,
+    /// The decoded output does not match the length recorded in the header.
+    LengthMismatch { expected: usize, actual: usize },
+    /// The file was written by a newer, unknown version of the format.
+    UnsupportedVersion(u8),
+    /// The frame declares a body encoding this version does not know.
+    UnsupportedMethod(u8),
+    /// The file is stored or coded without huffman codes, so it has no dictionary or tree.
+    NotHuffman,
+    /// The header stores code lengths, so counts are only known by decoding the payload.
+    NoCounts,
+    /// The header stores counts, and code lengths follow from the tree they build.
+    NoCodeLengths,
+    /// The decoded output does not match the checksum recorded in the trailer.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// Armored text is missing a marker or checksum line, or has characters outside its
+    /// encoding.
+    InvalidArmor,
+    /// The source is not an encrypted file.
+    NotEncrypted,
+    /// The header of an encrypted file names an unknown key derivation, or costs out of bounds.
+    InvalidKeyDerivation,
+    /// The passphrase is wrong, or the encrypted file was modified.
+    DecryptionFailed,
+    /// The system could not provide random bytes for a salt or nonce.
+    NoRandomness,
+    /// A block header fails its checksum, or does not follow on from the block before it.
+    InvalidBlock,
+    /// The source has a byte the codes given to encode it with do not cover.
+    NoCode(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "unexpected end of input"),
+            Self::InvalidByteSize(size) => write!(f, "invalid dictionary integer width: {}", size),
+            Self::InvalidDictionary => write!(f, "invalid dictionary"),
+            Self::LengthMismatch { expected, actual } => write!(
+                f,
+                "decoded {} bytes but the header expects {}",
+                actual, expected
+            ),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version: {}", version)
+            }
+            Self::UnsupportedMethod(method) => write!(f, "unsupported encoding method: {}", method),
+            Self::NotHuffman => write!(f, "file is not huffman coded"),
+            Self::NoCounts => write!(f, "file stores code lengths instead of counts"),
+            Self::NoCodeLengths => write!(f, "file stores counts instead of code lengths"),
+            Self::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum {:08x} does not match the expected {:08x}",
+                actual, expected
+            ),
+            Self::InvalidArmor => write!(f, "malformed armor"),
+            Self::NotEncrypted => write!(f, "file is not encrypted"),
+            Self::InvalidKeyDerivation => write!(f, "invalid key derivation parameters"),
+            Self::DecryptionFailed => write!(f, "wrong passphrase, or the file was modified"),
+            Self::NoRandomness => write!(f, "no random bytes available for encryption"),
+            Self::InvalidBlock => write!(f, "damaged block header"),
+            Self::NoCode(t) => write!(f, "no code for byte {}", t),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
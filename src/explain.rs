@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use crate::bytes::Codes;
+use crate::compress::{build_tree, source_dictionary};
+use crate::export::walk;
+use crate::huffman_tree::node::NodeRef;
+use crate::huffman_tree::tree::{self, HuffmanTree};
+use crate::huffman_tree::vecdeque::{Merge, Pick, Queue, Taken};
+use crate::stats::{character, code_string};
+
+use serde::Serialize;
+
+/// A trace of how the tree for a source is built, and how its first bytes are encoded and
+/// decoded again.
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    pub merges: Vec<Step>,
+    pub codes: Vec<Code>,
+    pub encoding: Vec<Code>,
+    pub bits: String,
+    pub decoding: Vec<Walk>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Step {
+    pub branch: usize,
+    pub left: Option<Operand>,
+    pub right: Option<Operand>,
+    pub freq: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Operand {
+    pub queue: &'static str,
+    pub node: String,
+    pub freq: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Code {
+    pub byte: u8,
+    pub character: String,
+    pub code: String,
+}
+
+/// The branches of the canonical tree visited from the root while decoding one symbol, numbered
+/// the way `huff tree` numbers nodes.
+#[derive(Debug, Serialize)]
+pub struct Walk {
+    pub path: Vec<Turn>,
+    pub byte: u8,
+    pub character: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Turn {
+    pub branch: usize,
+    pub bit: char,
+    pub to: String,
+}
+
+/// Explains the tree for the source and the coding of its first `bytes` bytes.
+pub fn explain(source: &[u8], bytes: usize) -> Explanation {
+    let (tokens, hits) = source_dictionary(source);
+    let (_, merges) = tree::traced_vecdeque(&tokens, &hits, source.len());
+    // Only the code lengths of the merged tree are kept; the codes are canonical.
+    let canonical = build_tree(source);
+    let key_pairs: Vec<(u8, Codes)> = canonical
+        .iter()
+        .flat_map(|tree| tree.stream_codes())
+        .map(|(t, code)| (t, code.to_codes()))
+        .collect();
+
+    let mut codes: Vec<Code> = key_pairs.iter().map(|(t, bits)| code(*t, bits)).collect();
+    codes.sort_by(|a, b| a.code.len().cmp(&b.code.len()).then(a.code.cmp(&b.code)));
+
+    let key_map: HashMap<u8, &Codes> = key_pairs.iter().map(|(t, bits)| (*t, bits)).collect();
+    let prefix = &source[..bytes.min(source.len())];
+    let encoding: Vec<Code> = prefix.iter().map(|t| code(*t, key_map[t])).collect();
+    let bits: Codes = prefix
+        .iter()
+        .flat_map(|t| key_map[t].iter().copied())
+        .collect();
+
+    Explanation {
+        merges: merges.iter().enumerate().map(step).collect(),
+        codes,
+        encoding,
+        bits: code_string(&bits),
+        decoding: canonical
+            .map(|tree| walks(&tree, &bits))
+            .unwrap_or_default(),
+    }
+}
+
+fn code(t: u8, bits: &[bool]) -> Code {
+    Code {
+        byte: t,
+        character: character(t),
+        code: code_string(bits),
+    }
+}
+
+fn step((branch, merge): (usize, &Merge<u8>)) -> Step {
+    Step {
+        branch,
+        left: merge.left.as_ref().map(operand),
+        right: merge.right.as_ref().map(operand),
+        freq: merge.freq,
+    }
+}
+
+fn operand(pick: &Pick<u8>) -> Operand {
+    Operand {
+        queue: match pick.queue {
+            Queue::Leaves => "leaves",
+            Queue::Branches => "branches",
+        },
+        node: name(&pick.node),
+        freq: pick.freq,
+    }
+}
+
+fn name(node: &Taken<u8>) -> String {
+    match node {
+        Taken::Leaf(t) => format!("\"{}\"", character(*t)),
+        Taken::Branch(branch) => format!("#{}", branch),
+    }
+}
+
+/// Decodes the bits by walking the tree from the root the way `HuffmanTree::read` does: a set
+/// bit goes to the left child.
+fn walks(tree: &HuffmanTree<u8>, bits: &[bool]) -> Vec<Walk> {
+    let ids: HashMap<usize, usize> = walk(tree.root())
+        .iter()
+        .map(|visit| (visit.node.index(), visit.id))
+        .collect();
+    let id = |node: NodeRef<u8>| ids[&node.index()];
+
+    let mut walks = Vec::new();
+    let mut path = Vec::new();
+    let mut node = tree.root();
+
+    for bit in bits {
+        let child = if *bit {
+            node.left_as_ref()
+        } else {
+            node.right_as_ref()
+        };
+        let child = match child {
+            Some(child) => child,
+            None => continue,
+        };
+
+        path.push(Turn {
+            branch: id(node),
+            bit: code_string(&[*bit]).chars().next().unwrap_or_default(),
+            to: match child.leaf() {
+                Some(t) => format!("\"{}\"", character(*t)),
+                None => format!("n{}", id(child)),
+            },
+        });
+
+        match child.leaf() {
+            Some(t) => {
+                walks.push(Walk {
+                    path: std::mem::take(&mut path),
+                    byte: *t,
+                    character: character(*t),
+                });
+                node = tree.root();
+            }
+            None => node = child,
+        }
+    }
+
+    walks
+}
+
+pub fn to_json(explanation: &Explanation) -> String {
+    serde_json::to_string_pretty(explanation).unwrap_or_default()
+}
+
+pub fn to_text(explanation: &Explanation) -> String {
+    let mut text = String::from(
+        "Tree construction\n\
+         Leaves wait in one queue sorted by count, merged branches in a second queue.\n\
+         Each step takes the two lowest nodes from the fronts of either queue.\n",
+    );
+    for step in &explanation.merges {
+        let operand = |operand: &Option<Operand>| match operand {
+            Some(o) => format!("{} ({}, from {})", o.node, o.freq, o.queue),
+            None => "nothing".to_string(),
+        };
+        text += &format!(
+            "#{} = {} + {} -> {}\n",
+            step.branch,
+            operand(&step.left),
+            operand(&step.right),
+            step.freq
+        );
+    }
+
+    text += "\nCodes\n\
+             Only the code lengths are kept. Codes are reassigned by counting up in order of\n\
+             length, then byte value, so the header needs nothing else.\n";
+    for code in &explanation.codes {
+        text += &format!("\"{}\"\t{}\n", code.character, code.code);
+    }
+
+    text += &format!(
+        "\nEncoding the first {} bytes\n",
+        explanation.encoding.len()
+    );
+    for code in &explanation.encoding {
+        text += &format!("\"{}\"\t-> {}\n", code.character, code.code);
+    }
+    text += &format!("Bits: {}\n", explanation.bits);
+
+    text += "\nDecoding\n";
+    for walk in &explanation.decoding {
+        let path: Vec<String> = walk
+            .path
+            .iter()
+            .map(|turn| format!("n{} -{}-> {}", turn.branch, turn.bit, turn.to))
+            .collect();
+        text += &format!("{}\temit \"{}\"\n", path.join(", "), walk.character);
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decoding_walk_round_trips() {
+        let input = b"geeksforgeeks";
+        let explanation = explain(input, input.len());
+
+        let decoded: Vec<u8> = explanation.decoding.iter().map(|w| w.byte).collect();
+        assert_eq!(&input[..], decoded.as_slice());
+        assert_eq!(7 - 1, explanation.merges.len());
+    }
+
+    #[test]
+    fn encoding_is_limited_to_prefix() {
+        let explanation = explain(b"aab", 2);
+
+        assert_eq!(2, explanation.encoding.len());
+        assert_eq!("00", explanation.bits);
+        assert_eq!(1, explanation.decoding[0].path.len());
+        assert_eq!("\"a\"", explanation.decoding[0].path[0].to);
+    }
+
+    #[test]
+    fn text_lists_merges() {
+        let text = to_text(&explain(b"aab", 3));
+
+        assert!(text.contains("#0 = \"b\" (1, from leaves) + \"a\" (2, from leaves) -> 3\n"));
+        assert!(text.contains("n0 -0-> \"a\"\temit \"a\"\n"));
+    }
+
+    #[test]
+    fn empty_source() {
+        let explanation = explain(b"", 16);
+
+        assert!(explanation.codes.is_empty());
+        assert!(explanation.decoding.is_empty());
+    }
+}
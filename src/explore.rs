@@ -0,0 +1,520 @@
+use std::io;
+
+use crate::compress::{decode, read_payload, read_tree, read_tree_counted, source_dictionary};
+use crate::error::Result;
+use crate::export::walk;
+use crate::format::{read_frame, Method};
+use crate::stats::{self, character, code_string, Symbol};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+
+const BYTES_PER_ROW: usize = 16;
+const BITS_PER_ROW: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pane {
+    Tree,
+    Codes,
+    Text,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sort {
+    Count,
+    Length,
+    Symbol,
+}
+
+#[derive(Debug)]
+pub struct TreeNode {
+    pub depth: usize,
+    pub freq: usize,
+    pub symbol: Option<u8>,
+    pub code: String,
+    pub collapsed: bool,
+}
+
+/// State of the explorer, kept apart from drawing so it can be driven without a terminal.
+pub struct Explorer {
+    pub focus: Pane,
+    pub tree: Vec<TreeNode>,
+    pub tree_cursor: usize,
+    pub symbols: Vec<Symbol>,
+    pub sort: Sort,
+    pub table_cursor: usize,
+    pub text: Vec<u8>,
+    pub bits: Vec<bool>,
+    /// `offsets[i]..offsets[i + 1]` are the payload bits of `text[i]`.
+    pub offsets: Vec<usize>,
+    pub cursor: usize,
+    pub anchor: Option<usize>,
+}
+
+impl Explorer {
+    /// Expects an encoded file.
+    pub fn new(source: &[u8]) -> Result<Self> {
+        let bits = read_payload(source)?;
+        // Canonical files are counted from their data, so decode them once and reuse it.
+        let (tree, text) = match read_frame(source)?.method {
+            Method::Canonical => {
+                let text = decode(source)?;
+                (read_tree_counted(source, source_dictionary(&text))?, text)
+            }
+            _ => {
+                let tree = read_tree(source)?;
+                let text = tree.read(bits.clone());
+                (tree, text)
+            }
+        };
+
+        let nodes = tree_nodes(&tree);
+        let symbols = stats::symbols(&tree, bits.len());
+
+        let mut lengths = [0; 256];
+        for symbol in &symbols {
+            lengths[symbol.byte as usize] = symbol.bits;
+        }
+        let mut offsets = Vec::with_capacity(text.len() + 1);
+        offsets.push(0);
+        for t in &text {
+            offsets.push(offsets[offsets.len() - 1] + lengths[*t as usize]);
+        }
+
+        Ok(Self {
+            focus: Pane::Tree,
+            tree: nodes,
+            tree_cursor: 0,
+            symbols,
+            sort: Sort::Count,
+            table_cursor: 0,
+            text,
+            bits,
+            offsets,
+            cursor: 0,
+            anchor: None,
+        })
+    }
+
+    /// Indices into `tree` of the nodes not hidden under a collapsed ancestor.
+    pub fn visible_nodes(&self) -> Vec<usize> {
+        let mut visible = Vec::new();
+        let mut hidden_below = None;
+
+        for (i, node) in self.tree.iter().enumerate() {
+            match hidden_below {
+                Some(depth) if node.depth > depth => continue,
+                _ => hidden_below = None,
+            }
+
+            visible.push(i);
+            if node.collapsed {
+                hidden_below = Some(node.depth);
+            }
+        }
+
+        visible
+    }
+
+    pub fn toggle(&mut self) {
+        if let Some(&i) = self.visible_nodes().get(self.tree_cursor) {
+            if self.tree[i].symbol.is_none() {
+                self.tree[i].collapsed = !self.tree[i].collapsed;
+            }
+        }
+    }
+
+    pub fn sort_by(&mut self, sort: Sort) {
+        self.sort = sort;
+        match sort {
+            Sort::Count => self
+                .symbols
+                .sort_by(|a, b| b.count.cmp(&a.count).then(a.byte.cmp(&b.byte))),
+            Sort::Length => self
+                .symbols
+                .sort_by(|a, b| a.bits.cmp(&b.bits).then(a.byte.cmp(&b.byte))),
+            Sort::Symbol => self.symbols.sort_by_key(|s| s.byte),
+        }
+    }
+
+    /// The selected range of decoded bytes, inclusive of the cursor.
+    pub fn selection(&self) -> (usize, usize) {
+        let anchor = self.anchor.unwrap_or(self.cursor);
+        (anchor.min(self.cursor), anchor.max(self.cursor) + 1)
+    }
+
+    /// The payload bits encoding the selected bytes.
+    pub fn selected_bits(&self) -> (usize, usize) {
+        let (start, end) = self.selection();
+        if self.text.is_empty() {
+            return (0, 0);
+        }
+
+        (self.offsets[start], self.offsets[end])
+    }
+
+    pub fn move_cursor(&mut self, delta: isize, extend: bool) {
+        if self.text.is_empty() {
+            return;
+        }
+
+        if extend && self.anchor.is_none() {
+            self.anchor = Some(self.cursor);
+        } else if !extend {
+            self.anchor = None;
+        }
+
+        let last = self.text.len() as isize - 1;
+        self.cursor = (self.cursor as isize + delta).clamp(0, last) as usize;
+    }
+
+    /// Applies a key press. Returns false when the explorer should close.
+    pub fn handle(&mut self, key: KeyEvent) -> bool {
+        let extend = key.modifiers.contains(KeyModifiers::SHIFT);
+
+        match (key.code, self.focus) {
+            (KeyCode::Char('q'), _) | (KeyCode::Esc, _) => return false,
+            (KeyCode::Tab, _) => {
+                self.focus = match self.focus {
+                    Pane::Tree => Pane::Codes,
+                    Pane::Codes => Pane::Text,
+                    Pane::Text => Pane::Tree,
+                }
+            }
+            (KeyCode::Up, Pane::Tree) => self.tree_cursor = self.tree_cursor.saturating_sub(1),
+            (KeyCode::Down, Pane::Tree) => {
+                self.tree_cursor = (self.tree_cursor + 1).min(self.visible_nodes().len() - 1)
+            }
+            (KeyCode::Enter, Pane::Tree) | (KeyCode::Char(' '), Pane::Tree) => self.toggle(),
+            (KeyCode::Up, Pane::Codes) => self.table_cursor = self.table_cursor.saturating_sub(1),
+            (KeyCode::Down, Pane::Codes) => {
+                self.table_cursor =
+                    (self.table_cursor + 1).min(self.symbols.len().saturating_sub(1))
+            }
+            (KeyCode::Char('c'), Pane::Codes) => self.sort_by(Sort::Count),
+            (KeyCode::Char('l'), Pane::Codes) => self.sort_by(Sort::Length),
+            (KeyCode::Char('s'), Pane::Codes) => self.sort_by(Sort::Symbol),
+            (KeyCode::Left, Pane::Text) => self.move_cursor(-1, extend),
+            (KeyCode::Right, Pane::Text) => self.move_cursor(1, extend),
+            (KeyCode::Up, Pane::Text) => self.move_cursor(-(BYTES_PER_ROW as isize), extend),
+            (KeyCode::Down, Pane::Text) => self.move_cursor(BYTES_PER_ROW as isize, extend),
+            _ => {}
+        }
+
+        true
+    }
+}
+
+fn tree_nodes(tree: &crate::huffman_tree::tree::HuffmanTree<u8>) -> Vec<TreeNode> {
+    let mut nodes: Vec<TreeNode> = Vec::new();
+
+    for visit in walk(tree.root()) {
+        let (depth, code) = match visit.parent {
+            Some((parent, bit)) => (
+                nodes[parent].depth + 1,
+                format!("{}{}", nodes[parent].code, bit),
+            ),
+            None => (0, String::new()),
+        };
+
+        nodes.push(TreeNode {
+            depth,
+            freq: visit.node.freq(),
+            symbol: visit.node.leaf().copied(),
+            code,
+            collapsed: false,
+        });
+    }
+
+    nodes
+}
+
+/// Opens the explorer on an encoded file and blocks until the user quits.
+pub fn run(source: &[u8]) -> io::Result<()> {
+    let mut explorer =
+        Explorer::new(source).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut explorer);
+    ratatui::try_restore()?;
+
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, explorer: &mut Explorer) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, explorer))?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !explorer.handle(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, explorer: &Explorer) {
+    let [main, help] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [left, right] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(main);
+    let [tree, codes] =
+        Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(left);
+    let [text, bits] = Layout::horizontal([
+        Constraint::Length((BYTES_PER_ROW + 2) as u16),
+        Constraint::Min(0),
+    ])
+    .areas(right);
+
+    draw_tree(frame, explorer, tree);
+    draw_codes(frame, explorer, codes);
+    draw_text(frame, explorer, text);
+    draw_bits(frame, explorer, bits);
+
+    frame.render_widget(
+        Paragraph::new(
+            "q quit  tab switch pane  enter toggle node  c/l/s sort by count/length/symbol  shift+arrows select",
+        )
+        .style(Style::default().add_modifier(Modifier::DIM)),
+        help,
+    );
+}
+
+fn block(title: &str, focused: bool) -> Block<'_> {
+    let style = if focused {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+
+    Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(style)
+}
+
+fn highlight() -> Style {
+    Style::default().add_modifier(Modifier::REVERSED)
+}
+
+/// First row to draw so that `row` stays within a window of `height` rows.
+fn scroll(row: usize, height: u16) -> usize {
+    let height = (height as usize).saturating_sub(2).max(1);
+    row.saturating_sub(height - 1)
+}
+
+fn draw_tree(frame: &mut Frame, explorer: &Explorer, area: Rect) {
+    let visible = explorer.visible_nodes();
+    let top = scroll(explorer.tree_cursor, area.height);
+
+    let lines: Vec<Line> = visible
+        .iter()
+        .enumerate()
+        .skip(top)
+        .take(area.height as usize)
+        .map(|(row, &i)| {
+            let node = &explorer.tree[i];
+            let marker = match (node.symbol, node.collapsed) {
+                (Some(_), _) => "  ",
+                (None, true) => "+ ",
+                (None, false) => "- ",
+            };
+            let label = match node.symbol {
+                Some(t) => format!("\"{}\" {} [{}]", character(t), node.freq, node.code),
+                None => format!("{} [{}]", node.freq, node.code),
+            };
+            let line = Line::from(format!("{}{}{}", "  ".repeat(node.depth), marker, label));
+
+            if row == explorer.tree_cursor {
+                line.style(highlight())
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(block("Tree", explorer.focus == Pane::Tree)),
+        area,
+    );
+}
+
+fn draw_codes(frame: &mut Frame, explorer: &Explorer, area: Rect) {
+    let title = match explorer.sort {
+        Sort::Count => "Codes (by count)",
+        Sort::Length => "Codes (by length)",
+        Sort::Symbol => "Codes (by symbol)",
+    };
+    let rows = explorer.symbols.iter().map(|symbol| {
+        Row::new(vec![
+            format!("\"{}\"", symbol.character),
+            symbol.count.to_string(),
+            symbol.code.clone(),
+            symbol.bits.to_string(),
+        ])
+    });
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Min(8),
+            Constraint::Length(4),
+        ],
+    )
+    .header(
+        Row::new(vec!["Character", "Count", "Code", "Bits"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .row_highlight_style(highlight())
+    .block(block(title, explorer.focus == Pane::Codes));
+
+    let mut state = TableState::default().with_selected(Some(explorer.table_cursor));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_text(frame: &mut Frame, explorer: &Explorer, area: Rect) {
+    let (start, end) = explorer.selection();
+    let top = scroll(explorer.cursor / BYTES_PER_ROW, area.height);
+
+    let lines: Vec<Line> = explorer
+        .text
+        .chunks(BYTES_PER_ROW)
+        .enumerate()
+        .skip(top)
+        .take(area.height as usize)
+        .map(|(row, chunk)| {
+            let spans: Vec<Span> = chunk
+                .iter()
+                .enumerate()
+                .map(|(column, t)| {
+                    let i = row * BYTES_PER_ROW + column;
+                    let glyph = if t.is_ascii_graphic() || *t == b' ' {
+                        (*t as char).to_string()
+                    } else {
+                        "·".to_string()
+                    };
+
+                    if i >= start && i < end && explorer.focus == Pane::Text || i == explorer.cursor
+                    {
+                        Span::styled(glyph, highlight())
+                    } else {
+                        Span::raw(glyph)
+                    }
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(block("Text", explorer.focus == Pane::Text)),
+        area,
+    );
+}
+
+fn draw_bits(frame: &mut Frame, explorer: &Explorer, area: Rect) {
+    let (start, end) = explorer.selected_bits();
+    let top = scroll(end.saturating_sub(1) / BITS_PER_ROW, area.height);
+
+    let lines: Vec<Line> = explorer
+        .bits
+        .chunks(BITS_PER_ROW)
+        .enumerate()
+        .skip(top)
+        .take(area.height as usize)
+        .map(|(row, chunk)| {
+            let offset = row * BITS_PER_ROW;
+            let chunk_end = offset + chunk.len();
+            let (from, to) = (start.clamp(offset, chunk_end), end.clamp(offset, chunk_end));
+
+            Line::from(vec![
+                Span::raw(code_string(&chunk[..from - offset])),
+                Span::styled(code_string(&chunk[from - offset..to - offset]), highlight()),
+                Span::raw(code_string(&chunk[to - offset..])),
+            ])
+        })
+        .collect();
+
+    let title = format!("Bits {}..{}", start, end);
+    frame.render_widget(Paragraph::new(lines).block(block(&title, false)), area);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compress::encode_huffman as encode;
+
+    #[test]
+    fn selection_maps_to_bits() {
+        // One bit per symbol, so byte and bit offsets line up.
+        let mut explorer = Explorer::new(&encode(b"aaab")).unwrap();
+
+        assert_eq!((0, 1), explorer.selected_bits());
+        explorer.move_cursor(3, false);
+        assert_eq!((3, 4), explorer.selected_bits());
+        explorer.move_cursor(-2, true);
+        assert_eq!((1, 4), explorer.selection());
+        assert_eq!((1, 4), explorer.selected_bits());
+    }
+
+    #[test]
+    fn collapse_hides_descendants() {
+        let mut explorer = Explorer::new(&encode(b"geeksforgeeks")).unwrap();
+        let all = explorer.visible_nodes().len();
+
+        explorer.toggle();
+        assert_eq!(vec![0], explorer.visible_nodes());
+        explorer.toggle();
+        assert_eq!(all, explorer.visible_nodes().len());
+    }
+
+    #[test]
+    fn draws_on_small_terminal() {
+        use ratatui::{backend::TestBackend, Terminal};
+
+        let mut explorer = Explorer::new(&encode(b"There and back again")).unwrap();
+        explorer.focus = Pane::Text;
+        explorer.move_cursor(5, true);
+
+        for (width, height) in [(80, 24), (10, 3)] {
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            terminal.draw(|frame| draw(frame, &explorer)).unwrap();
+        }
+    }
+
+    #[test]
+    fn sort_by_length() {
+        let mut explorer = Explorer::new(&encode(b"geeksforgeeks")).unwrap();
+        explorer.sort_by(Sort::Length);
+
+        let lengths: Vec<usize> = explorer.symbols.iter().map(|s| s.bits).collect();
+        let mut sorted = lengths.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, lengths);
+    }
+
+    #[test]
+    fn symbols_match_stats() {
+        let input = b"geeksforgeeks".repeat(20);
+        let source = crate::compress::encode(&input);
+        let explorer = Explorer::new(&source).unwrap();
+        assert_eq!(input, explorer.text);
+
+        let stats = stats::from(&source).unwrap().symbols;
+        let pairs = |symbols: &[Symbol]| -> Vec<(u8, usize, String)> {
+            symbols
+                .iter()
+                .map(|s| (s.byte, s.count, s.code.clone()))
+                .collect()
+        };
+        assert_eq!(pairs(&stats), pairs(&explorer.symbols));
+    }
+}
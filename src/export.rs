@@ -0,0 +1,185 @@
+use std::str::FromStr;
+
+use crate::bytes::BitFormat;
+use crate::huffman_tree::node::NodeRef;
+use crate::huffman_tree::tree::HuffmanTree;
+use crate::stats::character;
+
+use serde::Serialize;
+
+pub const FORMATS: &[&str] = &["dot", "json", "mermaid"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Dot,
+    Json,
+    Mermaid,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Self::Dot),
+            "json" => Ok(Self::Json),
+            "mermaid" => Ok(Self::Mermaid),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+/// Serialises the tree. Edges are labelled with the digit `stats` prints for that branch.
+pub fn export(tree: &HuffmanTree<u8>, format: Format) -> String {
+    match format {
+        Format::Dot => dot(tree.root()),
+        Format::Json => json(tree.root()),
+        Format::Mermaid => mermaid(tree.root()),
+    }
+}
+
+const LEFT: char = BitFormat::HUFF.polarity.digit(true);
+const RIGHT: char = BitFormat::HUFF.polarity.digit(false);
+
+pub(crate) struct Visit<'a> {
+    pub id: usize,
+    pub parent: Option<(usize, char)>,
+    pub node: NodeRef<'a, u8>,
+}
+
+/// Pre-order walk numbering every node, so exporters only deal with ids and edges.
+pub(crate) fn walk(root: NodeRef<'_, u8>) -> Vec<Visit<'_>> {
+    let mut visits = Vec::new();
+    let mut stack = vec![(None, root)];
+
+    while let Some((parent, node)) = stack.pop() {
+        let id = visits.len();
+        if let Some(right) = node.right_as_ref() {
+            stack.push((Some((id, RIGHT)), right));
+        }
+        if let Some(left) = node.left_as_ref() {
+            stack.push((Some((id, LEFT)), left));
+        }
+        visits.push(Visit { id, parent, node });
+    }
+
+    visits
+}
+
+fn dot(root: NodeRef<u8>) -> String {
+    let mut dot = String::from("digraph huffman {\n    node [shape=circle];\n");
+
+    for visit in walk(root) {
+        match visit.node.leaf() {
+            Some(t) => {
+                dot += &format!(
+                    "    n{} [shape=box, label=\"{}\\n{}\"];\n",
+                    visit.id,
+                    character(*t),
+                    visit.node.freq()
+                )
+            }
+            None => dot += &format!("    n{} [label=\"{}\"];\n", visit.id, visit.node.freq()),
+        }
+
+        if let Some((parent, bit)) = visit.parent {
+            dot += &format!("    n{} -> n{} [label=\"{}\"];\n", parent, visit.id, bit);
+        }
+    }
+
+    dot + "}\n"
+}
+
+fn mermaid(root: NodeRef<u8>) -> String {
+    let mut mermaid = String::from("graph TD\n");
+
+    for visit in walk(root) {
+        match visit.node.leaf() {
+            Some(t) => {
+                mermaid += &format!(
+                    "    n{}[\"{}: {}\"]\n",
+                    visit.id,
+                    character(*t).replace('"', "#quot;"),
+                    visit.node.freq()
+                )
+            }
+            None => mermaid += &format!("    n{}((\"{}\"))\n", visit.id, visit.node.freq()),
+        }
+
+        if let Some((parent, bit)) = visit.parent {
+            mermaid += &format!("    n{} -- {} --> n{}\n", parent, bit, visit.id);
+        }
+    }
+
+    mermaid
+}
+
+#[derive(Serialize)]
+struct JsonNode {
+    freq: usize,
+    code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    symbol: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    character: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    left: Option<Box<JsonNode>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    right: Option<Box<JsonNode>>,
+}
+
+impl JsonNode {
+    fn from(node: NodeRef<u8>, code: String) -> Self {
+        Self {
+            freq: node.freq(),
+            symbol: node.leaf().copied(),
+            character: node.leaf().map(|t| character(*t)),
+            left: node
+                .left_as_ref()
+                .map(|left| Box::new(Self::from(left, format!("{}{}", code, LEFT)))),
+            right: node
+                .right_as_ref()
+                .map(|right| Box::new(Self::from(right, format!("{}{}", code, RIGHT)))),
+            code,
+        }
+    }
+}
+
+fn json(root: NodeRef<u8>) -> String {
+    serde_json::to_string_pretty(&JsonNode::from(root, String::new())).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compress::build_tree;
+
+    #[test]
+    fn dot_edges() {
+        let tree = build_tree(b"aab").unwrap();
+        let dot = export(&tree, Format::Dot);
+
+        assert_eq!(
+            "digraph huffman {\n    node [shape=circle];\n    n0 [label=\"3\"];\n    n1 [shape=box, label=\"b\\n1\"];\n    n0 -> n1 [label=\"1\"];\n    n2 [shape=box, label=\"a\\n2\"];\n    n0 -> n2 [label=\"0\"];\n}\n",
+            dot
+        );
+    }
+
+    #[test]
+    fn json_codes_match_stats() {
+        let tree = build_tree(b"geeksforgeeks").unwrap();
+        let value: serde_json::Value = serde_json::from_str(&export(&tree, Format::Json)).unwrap();
+
+        assert_eq!(13, value["freq"]);
+        assert_eq!("", value["code"]);
+        assert_eq!("1", value["left"]["code"]);
+        assert_eq!("0", value["right"]["code"]);
+    }
+
+    #[test]
+    fn mermaid_escapes_quotes() {
+        let tree = build_tree(b"\"").unwrap();
+
+        assert!(export(&tree, Format::Mermaid).contains("n1[\"\\#quot;: 1\"]"));
+    }
+}
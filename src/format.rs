@@ -1,22 +1,374 @@
-use crate::bytes::{bytes_to_usize, read_be_u8, read_be_usize};
+use crate::bytes::{
+    bytes_to_usize, read_be_u32, read_be_u8, read_be_usize, read_varint, BitReader,
+};
+use crate::code_lengths;
+use crate::error::{Error, Result};
 
-pub fn read_dictionary(source: &[u8]) -> (Vec<u8>, Vec<usize>) {
-    let (tokens_len, remaining) = read_be_usize(source);
-    let (_compression_size, remaining) = read_be_usize(remaining);
-    let (byte_size, remaining) = read_be_u8(remaining);
+use serde::Serialize;
+
+/// Framed files start with the magic, a version byte and the method byte, and end with a
+/// CRC-32 and the length of the original data. Version 1 frames have no method byte and are
+/// always huffman coded. Files without the magic predate the frame and are read unchecked.
+pub const MAGIC: &[u8] = b"HUF";
+pub const VERSION: u8 = 2;
+const TRAILER_SIZE: usize = 4 + 8;
+
+/// How the body of a frame is encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    /// The huffman header, a dictionary of counts and the payload, as written before
+    /// canonical codes.
+    Huffman,
+    /// The original bytes, for sources that huffman coding would expand.
+    Stored,
+    /// The payload size as a varint, the code length of every byte and the payload coded
+    /// with canonical codes of those lengths.
+    Canonical,
+    /// Normalised counts and the payload coded with tANS, see `ans`.
+    Ans,
+    /// The output of the adaptive range coder, with no header at all, see `range_coder`.
+    Range,
+    /// Blocks compressed on their own, each a frame, see `blocks`.
+    Blocks,
+}
+
+impl Method {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Huffman => "huffman",
+            Self::Stored => "stored",
+            Self::Canonical => "canonical",
+            Self::Ans => "ans",
+            Self::Range => "range",
+            Self::Blocks => "blocks",
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Huffman => 0,
+            Self::Stored => 1,
+            Self::Canonical => 2,
+            Self::Ans => 3,
+            Self::Range => 4,
+            Self::Blocks => 5,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self> {
+        match byte {
+            0 => Ok(Self::Huffman),
+            1 => Ok(Self::Stored),
+            2 => Ok(Self::Canonical),
+            3 => Ok(Self::Ans),
+            4 => Ok(Self::Range),
+            5 => Ok(Self::Blocks),
+            _ => Err(Error::UnsupportedMethod(byte)),
+        }
+    }
+}
+
+pub struct Frame<'a> {
+    pub method: Method,
+    pub body: &'a [u8],
+    pub trailer: Option<Trailer>,
+}
+
+/// A byte alphabet never needs more than one dictionary entry per value.
+const MAX_TOKENS: usize = 256;
+
+pub struct Trailer {
+    pub checksum: u32,
+    pub length: usize,
+}
+
+impl Trailer {
+    pub fn verify(&self, output: &[u8]) -> Result<()> {
+        if output.len() != self.length {
+            return Err(Error::LengthMismatch {
+                expected: self.length,
+                actual: output.len(),
+            });
+        }
+
+        let checksum = crc32fast::hash(output);
+        if checksum != self.checksum {
+            return Err(Error::ChecksumMismatch {
+                expected: self.checksum,
+                actual: checksum,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+pub fn write_frame(method: Method, body: Vec<u8>, original: &[u8]) -> Vec<u8> {
+    [
+        write_head(method),
+        body,
+        write_trailer(crc32fast::hash(original), original.len()),
+    ]
+    .concat()
+}
+
+/// The magic, version and method that start a frame, for writers that stream the body.
+pub fn write_head(method: Method) -> Vec<u8> {
+    [MAGIC, &[VERSION, method.to_byte()]].concat()
+}
+
+/// The checksum and length of the original that end a frame.
+pub fn write_trailer(checksum: u32, length: usize) -> Vec<u8> {
+    [&checksum.to_be_bytes()[..], &length.to_be_bytes()].concat()
+}
+
+pub fn read_frame(source: &[u8]) -> Result<Frame<'_>> {
+    let framed = match source.strip_prefix(MAGIC) {
+        Some(framed) => framed,
+        None => {
+            return Ok(Frame {
+                method: Method::Huffman,
+                body: source,
+                trailer: None,
+            })
+        }
+    };
+
+    let (version, remaining) = read_be_u8(framed).ok_or(Error::Truncated)?;
+    let (method, remaining) = match version {
+        1 => (Method::Huffman, remaining),
+        2 => {
+            let (method, remaining) = read_be_u8(remaining).ok_or(Error::Truncated)?;
+            (Method::from_byte(method as u8)?, remaining)
+        }
+        _ => return Err(Error::UnsupportedVersion(version as u8)),
+    };
+
+    let body_len = remaining
+        .len()
+        .checked_sub(TRAILER_SIZE)
+        .ok_or(Error::Truncated)?;
+    let (body, trailer) = remaining.split_at(body_len);
+    let (checksum, trailer) = read_be_u32(trailer).ok_or(Error::Truncated)?;
+    let (length, _) = read_be_usize(trailer).ok_or(Error::Truncated)?;
+
+    Ok(Frame {
+        method,
+        body,
+        trailer: Some(Trailer { checksum, length }),
+    })
+}
+
+/// The tokens and counts of files that store counts rather than code lengths. Files encoded
+/// since canonical codes store none, see `compress::read_counts`.
+pub fn read_dictionary(source: &[u8]) -> Result<(Vec<u8>, Vec<usize>)> {
+    let frame = read_frame(source)?;
+    match frame.method {
+        Method::Huffman => (),
+        Method::Canonical => return Err(Error::NoCounts),
+        Method::Stored | Method::Ans | Method::Range | Method::Blocks => {
+            return Err(Error::NotHuffman)
+        }
+    }
+
+    let (tokens_len, size_when_compressed, byte_size, remaining) = read_fields(frame.body)?;
     let hits_len = tokens_len * (byte_size / 8);
 
     let tokens = remaining[..tokens_len].to_vec();
     let hits = bytes_to_usize(byte_size, &remaining[tokens_len..tokens_len + hits_len]);
 
-    (tokens, hits)
+    // Every code is at least one bit long, so the payload bounds the decoded length.
+    let size = hits
+        .iter()
+        .try_fold(0usize, |total, hit| total.checked_add(*hit))
+        .ok_or(Error::InvalidDictionary)?;
+    if size > size_when_compressed {
+        return Err(Error::InvalidDictionary);
+    }
+    // Zero counts could build chains deeper than a code can be long.
+    if hits.contains(&0) {
+        return Err(Error::InvalidDictionary);
+    }
+
+    Ok((tokens, hits))
+}
+
+/// The size of the dictionary, the payload size in bits, and the body after the sizes, where
+/// the payload follows the dictionary.
+pub fn read_sizes(source: &[u8]) -> Result<(usize, usize, &[u8])> {
+    let frame = read_frame(source)?;
+    match frame.method {
+        Method::Huffman => {
+            let (tokens_len, size_when_compressed, byte_size, remaining) = read_fields(frame.body)?;
+            let dictionary_size = tokens_len + tokens_len * (byte_size / 8);
+
+            Ok((dictionary_size, size_when_compressed, remaining))
+        }
+        Method::Canonical => {
+            let (_, dictionary_size, size_when_compressed, remaining) = read_canonical(frame.body)?;
+
+            Ok((dictionary_size, size_when_compressed, remaining))
+        }
+        Method::Stored | Method::Ans | Method::Range | Method::Blocks => Err(Error::NotHuffman),
+    }
+}
+
+/// The code length of every byte value in a canonical file, zero for absent bytes.
+pub fn read_code_lengths(source: &[u8]) -> Result<Vec<usize>> {
+    let frame = read_frame(source)?;
+    match frame.method {
+        Method::Canonical => read_canonical(frame.body).map(|(lengths, ..)| lengths),
+        Method::Huffman => Err(Error::NoCodeLengths),
+        Method::Stored | Method::Ans | Method::Range | Method::Blocks => Err(Error::NotHuffman),
+    }
+}
+
+fn read_canonical(body: &[u8]) -> Result<(Vec<usize>, usize, usize, &[u8])> {
+    let (size_when_compressed, remaining) = read_varint(body).ok_or(Error::Truncated)?;
+
+    let mut reader = BitReader::new(remaining);
+    let lengths = code_lengths::read(&mut reader)?;
+    let dictionary_size = reader.bytes_read();
+
+    let payload = remaining.get(dictionary_size..).ok_or(Error::Truncated)?;
+    if size_when_compressed.div_ceil(8) > payload.len() {
+        return Err(Error::Truncated);
+    }
+
+    Ok((lengths, dictionary_size, size_when_compressed, remaining))
 }
 
-pub fn read_sizes(source: &[u8]) -> (usize, usize, &[u8]) {
-    let (tokens_len, remaining) = read_be_usize(&source);
-    let (size_when_compressed, remaining) = read_be_usize(&remaining);
-    let (byte_size, remaining) = read_be_u8(&remaining);
+fn read_fields(body: &[u8]) -> Result<(usize, usize, usize, &[u8])> {
+    let (tokens_len, remaining) = read_be_usize(body).ok_or(Error::Truncated)?;
+    let (size_when_compressed, remaining) = read_be_usize(remaining).ok_or(Error::Truncated)?;
+    let (byte_size, remaining) = read_be_u8(remaining).ok_or(Error::Truncated)?;
+
+    if tokens_len > MAX_TOKENS {
+        return Err(Error::InvalidDictionary);
+    }
+
+    if !matches!(byte_size, 8 | 16 | 32 | 64) {
+        return Err(Error::InvalidByteSize(byte_size));
+    }
+
     let dictionary_size = tokens_len + tokens_len * (byte_size / 8);
+    let payload = remaining.get(dictionary_size..).ok_or(Error::Truncated)?;
+    if size_when_compressed.div_ceil(8) > payload.len() {
+        return Err(Error::Truncated);
+    }
+
+    Ok((tokens_len, size_when_compressed, byte_size, remaining))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compress::{encode_counts as encode, encode_huffman};
+
+    const HEADER: usize = 5;
+
+    #[test]
+    fn every_truncation_is_rejected() {
+        let source = encode(b"aaaaaabccccccddeeeee");
+
+        for end in 0..source.len() {
+            assert!(read_dictionary(&source[..end]).is_err(), "prefix {}", end);
+        }
+        assert!(read_dictionary(&source).is_ok());
+    }
+
+    #[test]
+    fn oversized_fields() {
+        let mut source = encode(b"geeksforgeeks");
+        source[HEADER..HEADER + 8].copy_from_slice(&usize::MAX.to_be_bytes());
+        assert_eq!(Err(Error::InvalidDictionary), read_dictionary(&source));
+
+        let mut source = encode(b"geeksforgeeks");
+        source[HEADER + 8..HEADER + 16].copy_from_slice(&usize::MAX.to_be_bytes());
+        assert_eq!(Err(Error::Truncated), read_dictionary(&source));
+
+        let mut source = encode(b"geeksforgeeks");
+        source[HEADER + 16] = 0;
+        assert_eq!(Err(Error::InvalidByteSize(0)), read_dictionary(&source));
+    }
+
+    #[test]
+    fn hits_exceeding_payload() {
+        let mut source = encode(b"geeksforgeeks");
+        let tokens_len = source[HEADER + 7] as usize;
+        source[HEADER + 17 + tokens_len] = u8::MAX;
+        assert_eq!(Err(Error::InvalidDictionary), read_dictionary(&source));
+    }
+
+    #[test]
+    fn zero_hits() {
+        let mut source = encode(b"geeksforgeeks");
+        let tokens_len = source[HEADER + 7] as usize;
+        source[HEADER + 17 + tokens_len] = 0;
+        assert_eq!(Err(Error::InvalidDictionary), read_dictionary(&source));
+    }
+
+    #[test]
+    fn version_1_has_no_method() {
+        let source = encode(b"geeksforgeeks");
+        let version_1 = [MAGIC, &[1], &source[HEADER..]].concat();
+
+        assert_eq!(
+            read_sizes(&source).unwrap(),
+            read_sizes(&version_1).unwrap()
+        );
+    }
+
+    #[test]
+    fn every_canonical_truncation_is_rejected() {
+        let source = encode_huffman(b"aaaaaabccccccddeeeee");
+
+        for end in 0..source.len() {
+            assert!(read_code_lengths(&source[..end]).is_err(), "prefix {}", end);
+        }
+        let lengths = read_code_lengths(&source).unwrap();
+        assert_eq!(vec![2, 3, 2, 3, 2], lengths[b'a' as usize..=b'e' as usize]);
+        assert_eq!(Err(Error::NoCounts), read_dictionary(&source));
+    }
+
+    #[test]
+    fn canonical_sizes() {
+        let source = encode_huffman(b"aaaaaabccccccddeeeee");
+        let (dictionary_size, size_when_compressed, remaining) = read_sizes(&source).unwrap();
+
+        assert_eq!(6 * 2 + 3 + 6 * 2 + 2 * 3 + 5 * 2, size_when_compressed);
+        assert_eq!(
+            remaining.len(),
+            dictionary_size + size_when_compressed.div_ceil(8)
+        );
+    }
+
+    #[test]
+    fn stored_has_no_dictionary() {
+        let source = write_frame(Method::Stored, b"abc".to_vec(), b"abc");
+
+        assert_eq!(Method::Stored, read_frame(&source).unwrap().method);
+        assert_eq!(Err(Error::NotHuffman), read_dictionary(&source));
+    }
+
+    #[test]
+    fn unknown_method() {
+        let mut source = encode(b"geeksforgeeks");
+        source[MAGIC.len() + 1] = 7;
+        assert_eq!(
+            Err(Error::UnsupportedMethod(7)),
+            read_sizes(&source).map(|_| ())
+        );
+    }
 
-    (dictionary_size, size_when_compressed, &remaining)
+    #[test]
+    fn unknown_version() {
+        let mut source = encode(b"geeksforgeeks");
+        source[MAGIC.len()] = VERSION + 1;
+        assert_eq!(
+            Err(Error::UnsupportedVersion(VERSION + 1)),
+            read_sizes(&source).map(|_| ())
+        );
+    }
 }
@@ -0,0 +1,155 @@
+//! Byte frequencies in a flat table. Consecutive bytes are counted in separate sub-histograms,
+//! so runs of the same byte do not wait on the previous increment of one counter.
+
+use std::thread;
+
+pub type Histogram = [u64; 256];
+
+/// Inputs at least this large are split across threads.
+const PARALLEL_THRESHOLD: usize = 8 << 20;
+
+/// Counts every byte, on all available cores when the input is large.
+pub fn count(data: &[u8]) -> Histogram {
+    if data.len() < PARALLEL_THRESHOLD {
+        return count_serial(data);
+    }
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    count_parallel(data, threads)
+}
+
+/// Counts with four interleaved sub-histograms on the current thread.
+pub fn count_serial(data: &[u8]) -> Histogram {
+    let mut tables = [[0u64; 256]; 4];
+
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        tables[0][chunk[0] as usize] += 1;
+        tables[1][chunk[1] as usize] += 1;
+        tables[2][chunk[2] as usize] += 1;
+        tables[3][chunk[3] as usize] += 1;
+    }
+    for byte in chunks.remainder() {
+        tables[0][*byte as usize] += 1;
+    }
+
+    let mut histogram = tables[0];
+    for table in &tables[1..] {
+        add(&mut histogram, table);
+    }
+
+    histogram
+}
+
+/// Counts equal slices of the input on separate threads and sums the results.
+pub fn count_parallel(data: &[u8], threads: usize) -> Histogram {
+    let chunk_size = data.len().div_ceil(threads.max(1)).max(1);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = data
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || count_serial(chunk)))
+            .collect();
+
+        let mut histogram = [0; 256];
+        for handle in handles {
+            let counts = handle.join().expect("counting threads do not panic");
+            add(&mut histogram, &counts);
+        }
+
+        histogram
+    })
+}
+
+/// The size of the chunks a sample is made of.
+pub const SAMPLE_CHUNK: usize = 1 << 16;
+
+/// Where the chunks of a sample of `percent` of the input start, spread evenly over it. Every
+/// chunk starts when the sample would cover the whole input.
+pub fn sample_starts(length: usize, percent: u8) -> Vec<usize> {
+    let wanted = (length as u128 * percent.min(100) as u128 / 100) as usize;
+    let chunks = wanted.div_ceil(SAMPLE_CHUNK).max(1);
+    if chunks >= length.div_ceil(SAMPLE_CHUNK) {
+        return (0..length).step_by(SAMPLE_CHUNK).collect();
+    }
+
+    // Fewer chunks than fit, so the stride is at least a chunk and the last one fits too.
+    let stride = length / chunks;
+    (0..chunks).map(|i| i * stride).collect()
+}
+
+/// Counts a sample of `percent` of the data, smoothed so every byte value has a code.
+pub fn sample(data: &[u8], percent: u8) -> Histogram {
+    let mut histogram = [0; 256];
+    for start in sample_starts(data.len(), percent) {
+        let end = data.len().min(start + SAMPLE_CHUNK);
+        add(&mut histogram, &count_serial(&data[start..end]));
+    }
+    smooth(&mut histogram);
+
+    histogram
+}
+
+/// Adds one to every count, so bytes a sample missed still get a (long) code.
+pub fn smooth(histogram: &mut Histogram) {
+    histogram.iter_mut().for_each(|count| *count += 1);
+}
+
+/// Adds the counts of `other` to `histogram`.
+pub fn add(histogram: &mut Histogram, other: &Histogram) {
+    histogram.iter_mut().zip(other).for_each(|(a, b)| *a += b);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn naive(data: &[u8]) -> Histogram {
+        let mut histogram = [0; 256];
+        data.iter().for_each(|b| histogram[*b as usize] += 1);
+        histogram
+    }
+
+    #[test]
+    fn matches_naive_count() {
+        let data: Vec<u8> = (0..10_007u32).map(|i| (i * i % 251) as u8).collect();
+
+        for len in [0, 1, 3, 4, 5, 1000, data.len()] {
+            assert_eq!(naive(&data[..len]), count_serial(&data[..len]), "{}", len);
+        }
+    }
+
+    #[test]
+    fn parallel_matches_serial() {
+        let data: Vec<u8> = (0..100_003u32).map(|i| (i % 7 * 31) as u8).collect();
+
+        for threads in [0, 1, 3, 8] {
+            assert_eq!(count_serial(&data), count_parallel(&data, threads));
+        }
+        assert_eq!([0; 256], count_parallel(&[], 4));
+    }
+
+    #[test]
+    fn sample_chunks_are_spread_out() {
+        let length = 100 * SAMPLE_CHUNK + 5;
+
+        assert_eq!(vec![0, length / 2], sample_starts(length, 2));
+        assert_eq!(101, sample_starts(length, 100).len());
+        assert_eq!(vec![0], sample_starts(10, 1));
+        assert!(sample_starts(0, 50).is_empty());
+        for percent in [1, 7, 33, 99] {
+            let starts = sample_starts(length, percent);
+            assert!(starts.windows(2).all(|w| w[1] - w[0] >= SAMPLE_CHUNK));
+            assert!(starts.last().unwrap() + SAMPLE_CHUNK <= length);
+        }
+    }
+
+    #[test]
+    fn sample_counts_every_byte() {
+        let data = vec![b'a'; 10 * SAMPLE_CHUNK];
+        let histogram = sample(&data, 10);
+
+        assert_eq!(SAMPLE_CHUNK as u64 + 1, histogram[b'a' as usize]);
+        assert!(histogram.iter().all(|count| *count > 0));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::bytes::Codes;
+
+/// Assigns canonical codes: symbols ordered by code length, then by value, count up from zero
+/// and the count is shifted left whenever the length grows. Only the lengths need storing to
+/// rebuild them. Symbols of length zero get no code. Returns None when the lengths
+/// oversubscribe the code space.
+pub fn codes<T>(lengths: &[(T, usize)]) -> Option<Vec<(T, Codes)>>
+where
+    T: Ord + Clone,
+{
+    let mut sorted: Vec<&(T, usize)> = lengths.iter().filter(|(_, l)| *l > 0).collect();
+    sorted.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+
+    let mut code = Codes::new();
+    let mut codes = Vec::with_capacity(sorted.len());
+    for (i, (t, length)) in sorted.into_iter().enumerate() {
+        if i > 0 {
+            increment(&mut code)?;
+        }
+        code.resize(*length, false);
+        codes.push((t.clone(), code.clone()));
+    }
+
+    Some(codes)
+}
+
+/// Adds one to the code read as a big-endian binary number. None on overflow.
+fn increment(code: &mut Codes) -> Option<()> {
+    for bit in code.iter_mut().rev() {
+        *bit = !*bit;
+        if *bit {
+            return Some(());
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deflate_example() {
+        // RFC 1951, 3.2.2: lengths (3, 3, 3, 3, 3, 2, 4, 4) for A to H.
+        let lengths: Vec<(u8, usize)> = b"ABCDEFGH"
+            .iter()
+            .copied()
+            .zip(vec![3, 3, 3, 3, 3, 2, 4, 4])
+            .collect();
+
+        let codes: Vec<(u8, String)> = codes(&lengths)
+            .unwrap()
+            .into_iter()
+            .map(|(t, code)| (t, code.iter().map(|b| if *b { '1' } else { '0' }).collect()))
+            .collect();
+
+        let expected = vec![
+            (b'F', "00"),
+            (b'A', "010"),
+            (b'B', "011"),
+            (b'C', "100"),
+            (b'D', "101"),
+            (b'E', "110"),
+            (b'G', "1110"),
+            (b'H', "1111"),
+        ];
+        assert_eq!(
+            expected
+                .into_iter()
+                .map(|(t, c)| (t, c.to_string()))
+                .collect::<Vec<_>>(),
+            codes
+        );
+    }
+
+    #[test]
+    fn oversubscribed() {
+        assert!(codes(&[(0, 1), (1, 1), (2, 1)]).is_none());
+        assert!(codes(&[(0, 1), (1, 0), (2, 1)]).is_some());
+    }
+}
@@ -3,18 +3,16 @@
 //! 2. Traverse the Huffman Tree and assign codes to characters.
 //!     1. Create leaf node for each unique character and build a min heap of all leaf nodes.
 
-use super::node::{Node, T};
-use std::fmt::{Debug, Display};
+use super::node::{Arena, Node};
+use std::fmt::Debug;
 
+/// Arena indices of nodes, ordered by frequency.
 #[derive(Debug)]
 struct MinHeap {
-    array: Vec<Node<T>>,
+    array: Vec<(usize, usize)>,
 }
 
-impl MinHeap
-where
-    T: Display + Debug
-{
+impl MinHeap {
     fn with_capacity(size: usize) -> Self {
         Self {
             array: Vec::with_capacity(size),
@@ -30,11 +28,11 @@ where
         let left = 2 * index + 1;
         let right = 2 * index + 2;
 
-        if left < self.size() && self.array[left].freq < self.array[smallest].freq {
+        if left < self.size() && self.array[left].0 < self.array[smallest].0 {
             smallest = left;
         }
 
-        if right < self.size() && self.array[right].freq < self.array[smallest].freq {
+        if right < self.size() && self.array[right].0 < self.array[smallest].0 {
             smallest = right;
         }
 
@@ -49,30 +47,27 @@ where
     }
 
     fn freq_at(&self, index: usize) -> usize {
-        self.array[index].freq
+        self.array[index].0
     }
 
     fn is_size_one(&self) -> bool {
         self.size() == 1
     }
 
-    fn extract_min(&mut self) -> Option<Node<T>> {
+    fn extract_min(&mut self) -> Option<(usize, usize)> {
         if self.array.is_empty() {
             return None;
         }
-        self.swap_nodes(0, self.size() - 1);
+        let last = self.size() - 1;
+        self.swap_nodes(0, last);
         let node = self.array.pop();
         self.min_heapify(0);
 
         node
     }
 
-    fn insert_min_heap(&mut self, node: Node<T>) {
-        let node_freq = node.freq;
-        self.array.push(node);
-        if self.array.is_empty() {
-            return;
-        }
+    fn insert_min_heap(&mut self, node_freq: usize, index: usize) {
+        self.array.push((node_freq, index));
         let mut i = self.size() - 1;
 
         while i > 0 && node_freq < self.freq_at((i - 1) / 2) {
@@ -82,66 +77,74 @@ where
     }
 }
 
-pub fn from(data: &[T], freqs: Vec<usize>, size: usize) -> Option<Node<T>>
+/// Builds the tree with a binary min-heap. Unlike the two-queue builder the leaves need not
+/// be sorted by frequency.
+pub fn from<T>(data: &[T], freqs: &[usize]) -> Option<Arena<T>>
 where
-    T: Display + Debug,
+    T: Debug + Clone,
 {
-    let mut heap = MinHeap::with_capacity(size);
+    let mut arena = Arena::with_capacity(data.len());
+    let mut heap = MinHeap::with_capacity(data.len());
 
-    data.into_iter().zip(freqs.iter()).for_each(|(d, f)| {
-        heap.array.push(Node::new_leaf(*d, *f));
+    data.iter().zip(freqs.iter()).for_each(|(d, f)| {
+        heap.insert_min_heap(*f, arena.push(Node::new_leaf(d.clone(), *f)));
     });
 
     while !heap.is_size_one() && !heap.array.is_empty() {
-        let mut left = heap.extract_min();
-        let mut right = heap.extract_min();
+        let left = heap.extract_min();
+        let right = heap.extract_min();
 
-        let top_freq = left.as_ref().map(|l| l.freq).unwrap_or_default()
-            + right.as_ref().map(|r| r.freq).unwrap_or_default();
-        let mut top = Node::new_branch(top_freq);
+        let top_freq =
+            left.map(|l| l.0).unwrap_or_default() + right.map(|r| r.0).unwrap_or_default();
+        let top = Node::new_branch(top_freq, left.map(|l| l.1), right.map(|r| r.1));
 
-        if let Some(node) = left.take() {
-            top.left(Box::new(node));
-        }
+        heap.insert_min_heap(top_freq, arena.push(top));
+    }
 
-        if let Some(node) = right.take() {
-            top.right(Box::new(node));
+    match heap.extract_min() {
+        // A lone leaf still needs a one bit code.
+        Some((freq, index)) if arena.get(index).leaf().is_some() => {
+            arena.push(Node::new_branch(freq, Some(index), None));
+            Some(arena)
         }
-
-        heap.insert_min_heap(top);
+        Some(_) => Some(arena),
+        None => None,
     }
-
-    heap.extract_min()
 }
 
-#[cfg(foo)]
+#[cfg(test)]
 mod test {
-    use super::super::codes::Codes;
+    use super::super::tree::HuffmanTree;
     use super::*;
 
-    //#[test]
+    #[test]
     fn _1() {
-        let array = vec!['a', 'b', 'c', 'd', 'e', 'f'];
+        let array = vec![b'a', b'b', b'c', b'd', b'e', b'f'];
         let freq = vec![5, 9, 12, 13, 16, 45];
 
-        let size = array.len();
-
-        let (actual_array, codes): (Vec<String>, Vec<Codes>) =
-            from(array, freq, size).into_iter().unzip();
-
-        let expected_codes: Vec<Codes> = vec![
-            vec![false],
-            vec![true, false, false],
-            vec![true, false, true],
-            vec![true, true, false, false],
-            vec![true, true, false, true],
-            vec![true, true, true],
-        ]
-        .into_iter()
-        .map(|c| Codes::from(c))
-        .collect();
-        let expected_array = vec!["f", "c", "d", "a", "b", "e"];
-        assert_eq!(expected_array, actual_array);
-        assert_eq!(expected_codes, codes);
+        let mut lengths: Vec<(u8, usize)> = HuffmanTree::from(from(&array, &freq).unwrap(), 6)
+            .stream_codes()
+            .into_iter()
+            .map(|(t, code)| (t, code.len))
+            .collect();
+        lengths.sort();
+
+        let expected = vec![
+            (b'a', 4),
+            (b'b', 4),
+            (b'c', 3),
+            (b'd', 3),
+            (b'e', 3),
+            (b'f', 1),
+        ];
+        assert_eq!(expected, lengths);
+    }
+
+    #[test]
+    fn unsorted_leaves() {
+        let arena = from(b"fab", &[45, 5, 9]).unwrap();
+        let root = arena.root().unwrap();
+        assert_eq!(59, root.freq());
+        assert_eq!(Some(&b'f'), root.right_as_ref().and_then(|n| n.leaf()));
     }
 }
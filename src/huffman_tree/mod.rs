@@ -1,3 +1,7 @@
-mod node;
+pub mod canonical;
+pub mod min_heap;
+pub mod moffat;
+pub mod node;
+pub mod strategy;
 pub mod tree;
-mod vecdeque;
+pub mod vecdeque;
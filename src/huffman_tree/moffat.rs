@@ -0,0 +1,90 @@
+//! In-place code lengths for frequencies sorted in ascending order, after Moffat and
+//! Katajainen, "In-Place Calculation of Minimum-Redundancy Codes" (1995). No tree is built:
+//! the frequencies are overwritten with parent pointers, then depths, then code lengths.
+
+/// Replaces each frequency with the code length of its symbol. Lengths come out in descending
+/// order. The frequencies must be sorted in ascending order.
+pub fn lengths(a: &mut [usize]) {
+    let n = a.len();
+    if n <= 1 {
+        // A lone symbol still needs a one bit code.
+        a.iter_mut().for_each(|length| *length = 1);
+        return;
+    }
+
+    // Merge like the two-queue builder, leaving the index of each branch's parent behind.
+    a[0] += a[1];
+    let (mut root, mut leaf) = (0, 2);
+    for next in 1..n - 1 {
+        if leaf >= n || a[root] < a[leaf] {
+            a[next] = a[root];
+            a[root] = next;
+            root += 1;
+        } else {
+            a[next] = a[leaf];
+            leaf += 1;
+        }
+
+        if leaf >= n || (root < next && a[root] < a[leaf]) {
+            a[next] += a[root];
+            a[root] = next;
+            root += 1;
+        } else {
+            a[next] += a[leaf];
+            leaf += 1;
+        }
+    }
+
+    // Turn the parent pointers into branch depths, the root last.
+    a[n - 2] = 0;
+    for next in (0..n - 2).rev() {
+        a[next] = a[a[next]] + 1;
+    }
+
+    // Count the branches at each depth to hand out leaf depths from the back.
+    let (mut available, mut used, mut depth) = (1, 0, 0);
+    let mut root = n as isize - 2;
+    let mut next = n as isize - 1;
+    while available > 0 {
+        while root >= 0 && a[root as usize] == depth {
+            used += 1;
+            root -= 1;
+        }
+        while available > used {
+            a[next as usize] = depth;
+            next -= 1;
+            available -= 1;
+        }
+        available = 2 * used;
+        depth += 1;
+        used = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn geeksforgeeks() {
+        let mut a = vec![5, 9, 12, 13, 16, 45];
+        lengths(&mut a);
+
+        assert_eq!(vec![4, 4, 3, 3, 3, 1], a);
+    }
+
+    #[test]
+    fn small() {
+        let mut a = vec![];
+        lengths(&mut a);
+        assert!(a.is_empty());
+
+        let mut a = vec![7];
+        lengths(&mut a);
+        assert_eq!(vec![1], a);
+
+        let mut a = vec![1, 1];
+        lengths(&mut a);
+        assert_eq!(vec![1, 1], a);
+    }
+}
@@ -1,122 +1,148 @@
-use std::cmp::{Ordering, PartialEq, PartialOrd};
 use std::fmt::Debug;
 
-type BoxOption<T> = Option<Box<T>>;
-
 #[derive(Clone, Debug)]
 enum NodeType<T> {
     Leaf(T),
     Branch {
-        left: BoxOption<Node<T>>,
-        right: BoxOption<Node<T>>,
+        left: Option<usize>,
+        right: Option<usize>,
     },
 }
 
-impl<T> NodeType<T> {
-    fn leaf(&self) -> Option<&T> {
-        match self {
-            Self::Leaf(t) => Some(t),
-            _ => None,
+/// A node of a tree stored in an `Arena`. Children are indices into the same arena.
+#[derive(Clone, Debug)]
+pub struct Node<T> {
+    pub freq: usize,
+    node: NodeType<T>,
+}
+
+impl<T> Node<T>
+where
+    T: Debug,
+{
+    pub fn new_branch(freq: usize, left: Option<usize>, right: Option<usize>) -> Self {
+        Self {
+            freq,
+            node: NodeType::Branch { left, right },
         }
     }
 
-    fn left(&mut self, node: Box<Node<T>>) {
-        if let Self::Branch {
-            ref mut left,
-            right: _,
-        } = *self
-        {
-            *left = Some(node);
+    pub fn new_leaf(data: T, freq: usize) -> Self {
+        Self {
+            freq,
+            node: NodeType::Leaf(data),
         }
     }
 
-    fn right(&mut self, node: Box<Node<T>>) {
-        if let Self::Branch {
-            left: _,
-            ref mut right,
-        } = *self
-        {
-            *right = Some(node);
+    pub fn left(&self) -> Option<usize> {
+        match self.node {
+            NodeType::Branch { left, .. } => left,
+            NodeType::Leaf(_) => None,
         }
     }
 
-    fn left_as_ref(&self) -> Option<&Node<T>> {
-        if let Self::Branch { ref left, right: _ } = *self {
-            if let Some(ref node) = *left {
-                return Some(node.as_ref());
-            }
+    pub fn right(&self) -> Option<usize> {
+        match self.node {
+            NodeType::Branch { right, .. } => right,
+            NodeType::Leaf(_) => None,
         }
-
-        None
     }
 
-    fn right_as_ref(&self) -> Option<&Node<T>> {
-        if let Self::Branch { left: _, ref right } = *self {
-            if let Some(ref node) = *right {
-                return Some(node.as_ref());
-            }
+    pub fn leaf(&self) -> Option<&T> {
+        match &self.node {
+            NodeType::Leaf(t) => Some(t),
+            NodeType::Branch { .. } => None,
         }
-
-        None
     }
 }
 
+/// Every node of a tree in one allocation. Children are added before their parents, so the
+/// root is the last node.
 #[derive(Clone, Debug)]
-pub struct Node<T> {
-    pub freq: usize,
-    node: NodeType<T>,
+pub struct Arena<T> {
+    nodes: Vec<Node<T>>,
 }
 
-impl<T> Node<T>
+impl<T> Arena<T>
 where
     T: Debug,
 {
-    pub fn new_branch(freq: usize) -> Self {
+    /// Room for a full tree over this many leaves.
+    pub fn with_capacity(leaves: usize) -> Self {
         Self {
-            freq,
-            node: NodeType::Branch {
-                left: None,
-                right: None,
-            },
+            nodes: Vec::with_capacity((2 * leaves).saturating_sub(1)),
         }
     }
 
-    pub fn new_leaf(data: T, freq: usize) -> Self {
-        Self {
-            freq,
-            node: NodeType::Leaf(data),
-        }
+    /// Adds the node, returning its index.
+    pub fn push(&mut self, node: Node<T>) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
     }
 
-    pub fn left(&mut self, node: Box<Node<T>>) {
-        self.node.left(node);
+    pub fn get(&self, index: usize) -> &Node<T> {
+        &self.nodes[index]
     }
 
-    pub fn right(&mut self, node: Box<Node<T>>) {
-        self.node.right(node);
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
     }
 
-    pub fn left_as_ref(&self) -> Option<&Self> {
-        self.node.left_as_ref()
+    pub fn root(&self) -> Option<NodeRef<'_, T>> {
+        self.nodes
+            .len()
+            .checked_sub(1)
+            .map(|index| self.node(index))
     }
 
-    pub fn right_as_ref(&self) -> Option<&Self> {
-        self.node.right_as_ref()
+    pub fn node(&self, index: usize) -> NodeRef<'_, T> {
+        NodeRef { arena: self, index }
     }
+}
 
-    pub fn leaf(&self) -> Option<&T> {
-        self.node.leaf()
-    }
+/// A node together with the arena its children are in, to walk the tree.
+#[derive(Debug)]
+pub struct NodeRef<'a, T> {
+    arena: &'a Arena<T>,
+    index: usize,
 }
 
-impl<T> PartialOrd for Node<T> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.freq.cmp(&other.freq))
+impl<T> Clone for NodeRef<'_, T> {
+    fn clone(&self) -> Self {
+        *self
     }
 }
 
-impl<T> PartialEq for Node<T> {
-    fn eq(&self, other: &Self) -> bool {
-        self.freq == other.freq
+impl<T> Copy for NodeRef<'_, T> {}
+
+impl<'a, T> NodeRef<'a, T>
+where
+    T: Debug,
+{
+    /// The position in the arena, unique within the tree.
+    pub fn index(self) -> usize {
+        self.index
+    }
+
+    pub fn freq(self) -> usize {
+        self.arena.get(self.index).freq
+    }
+
+    pub fn leaf(self) -> Option<&'a T> {
+        self.arena.get(self.index).leaf()
+    }
+
+    pub fn left_as_ref(self) -> Option<Self> {
+        self.arena
+            .get(self.index)
+            .left()
+            .map(|i| self.arena.node(i))
+    }
+
+    pub fn right_as_ref(self) -> Option<Self> {
+        self.arena
+            .get(self.index)
+            .right()
+            .map(|i| self.arena.node(i))
     }
 }
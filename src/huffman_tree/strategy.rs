@@ -0,0 +1,129 @@
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use super::node::Arena;
+use super::tree::HuffmanTree;
+use super::{min_heap, moffat, vecdeque};
+
+pub const STRATEGIES: &[&str] = &["two-queue", "heap", "moffat"];
+
+/// How code lengths are computed. All strategies give codes of the same total size, but when
+/// counts tie they may pick different lengths, so the encoded bytes can differ.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Strategy {
+    /// Leaves and merged branches in two queues.
+    #[default]
+    TwoQueue,
+    /// A binary min-heap of nodes.
+    Heap,
+    /// Moffat and Katajainen's in-place calculation, without building a tree.
+    Moffat,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "two-queue" => Ok(Self::TwoQueue),
+            "heap" => Ok(Self::Heap),
+            "moffat" => Ok(Self::Moffat),
+            _ => Err(format!("unknown strategy: {}", s)),
+        }
+    }
+}
+
+impl Strategy {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TwoQueue => "two-queue",
+            Self::Heap => "heap",
+            Self::Moffat => "moffat",
+        }
+    }
+
+    /// The code length of every token. The frequencies must be sorted in ascending order.
+    pub fn lengths<T>(self, data: &[T], freqs: &[usize]) -> Vec<(T, usize)>
+    where
+        T: Debug + Clone,
+    {
+        match self {
+            Self::TwoQueue => tree_lengths(vecdeque::from(data, freqs), data.len()),
+            Self::Heap => tree_lengths(min_heap::from(data, freqs), data.len()),
+            Self::Moffat => {
+                let mut lengths = freqs.to_vec();
+                moffat::lengths(&mut lengths);
+                data.iter().cloned().zip(lengths).collect()
+            }
+        }
+    }
+}
+
+fn tree_lengths<T>(tree: Option<Arena<T>>, size: usize) -> Vec<(T, usize)>
+where
+    T: Debug + Clone,
+{
+    tree.map(|tree| HuffmanTree::from(tree, size).stream_codes())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(t, code)| (t, code.len))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cost(lengths: &[(usize, usize)], freqs: &[usize]) -> usize {
+        lengths.iter().map(|(i, length)| freqs[*i] * length).sum()
+    }
+
+    fn kraft(lengths: &[(usize, usize)]) -> f64 {
+        lengths
+            .iter()
+            .map(|(_, length)| 0.5f64.powi(*length as i32))
+            .sum()
+    }
+
+    #[test]
+    fn equivalent_lengths() {
+        let cases: Vec<Vec<usize>> = vec![
+            vec![],
+            vec![3],
+            vec![1, 1],
+            vec![5, 9, 12, 13, 16, 45],
+            vec![1, 1, 1, 1, 1, 1, 1, 1],
+            vec![1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89],
+            (1..=256).map(|i| i * i % 97 + 1).collect(),
+        ];
+
+        for mut freqs in cases {
+            freqs.sort();
+            let data: Vec<usize> = (0..freqs.len()).collect();
+
+            let two_queue = Strategy::TwoQueue.lengths(&data, &freqs);
+            for strategy in [Strategy::Heap, Strategy::Moffat] {
+                let lengths = strategy.lengths(&data, &freqs);
+
+                assert_eq!(freqs.len(), lengths.len(), "{:?}", strategy);
+                assert_eq!(
+                    cost(&two_queue, &freqs),
+                    cost(&lengths, &freqs),
+                    "{:?} {:?}",
+                    strategy,
+                    freqs
+                );
+                if freqs.len() > 1 {
+                    assert_eq!(1.0, kraft(&lengths), "{:?} {:?}", strategy, freqs);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for name in STRATEGIES {
+            assert_eq!(*name, name.parse::<Strategy>().unwrap().name());
+        }
+    }
+}
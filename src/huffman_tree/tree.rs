@@ -1,22 +1,97 @@
 use std::fmt::Debug;
 
-use super::node::Node;
-use super::vecdeque;
-use crate::bytes::Codes;
+use super::node::{Arena, Node, NodeRef};
+use super::vecdeque::{self, Merge};
+use crate::bytes::{Code, Codes};
 
 pub fn with_vecdeque<T>(data: &[T], freqs: &[usize], size: usize) -> Option<HuffmanTree<T>>
 where
     T: Debug + Clone,
 {
-    if let Some(tree) = vecdeque::from(data, freqs, size) {
+    if let Some(tree) = vecdeque::from(data, freqs) {
         return Some(HuffmanTree::from(tree, size));
     }
 
     None
 }
 
+/// Like `with_vecdeque`, also returning the merges that built the tree.
+pub fn traced_vecdeque<T>(
+    data: &[T],
+    freqs: &[usize],
+    size: usize,
+) -> (Option<HuffmanTree<T>>, Vec<Merge<T>>)
+where
+    T: Debug + Clone,
+{
+    let (tree, merges) = vecdeque::traced(data, freqs);
+    (tree.map(|tree| HuffmanTree::from(tree, size)), merges)
+}
+
+/// Builds the tree that reads the given codes, e.g. canonical codes rebuilt from code lengths.
+/// Leaves carry the given counts and branches the sum of their children. Returns None when
+/// there are no codes, one is a prefix of another or one is longer than `Code::MAX_LEN`.
+pub fn with_codes<T>(
+    data: &[T],
+    codes: &[Codes],
+    freqs: &[usize],
+    size: usize,
+) -> Option<HuffmanTree<T>>
+where
+    T: Debug + Clone,
+{
+    // Sorted, a code is followed by the codes it is a prefix of.
+    let mut sorted = codes.to_vec();
+    sorted.sort();
+    if sorted.windows(2).any(|pair| pair[1].starts_with(&pair[0]))
+        || codes.iter().any(|code| code.len() > Code::MAX_LEN)
+    {
+        return None;
+    }
+
+    let leaves: Vec<(&T, &[bool], usize)> = data
+        .iter()
+        .zip(codes.iter())
+        .zip(freqs.iter())
+        .map(|((t, code), freq)| (t, code.as_slice(), *freq))
+        .collect();
+
+    let mut arena = Arena::with_capacity(leaves.len());
+    node_from(&mut arena, leaves)?;
+
+    Some(HuffmanTree::from(arena, size))
+}
+
+/// Adds the subtree of the leaves to the arena, children first, returning its index.
+fn node_from<T>(arena: &mut Arena<T>, leaves: Vec<(&T, &[bool], usize)>) -> Option<usize>
+where
+    T: Debug + Clone,
+{
+    if let [(t, [], freq)] = leaves.as_slice() {
+        return Some(arena.push(Node::new_leaf((*t).clone(), *freq)));
+    }
+    if leaves.is_empty() {
+        return None;
+    }
+
+    let (left, right): (Vec<_>, Vec<_>) = leaves
+        .into_iter()
+        .map(|(t, code, freq)| (code[0], (t, &code[1..], freq)))
+        .partition(|(bit, _)| *bit);
+    let left = node_from(arena, left.into_iter().map(|(_, leaf)| leaf).collect());
+    let right = node_from(arena, right.into_iter().map(|(_, leaf)| leaf).collect());
+
+    let freq = left
+        .iter()
+        .chain(right.iter())
+        .map(|i| arena.get(*i).freq)
+        .sum();
+    Some(arena.push(Node::new_branch(freq, left, right)))
+}
+
+/// A tree in an arena that is never empty, the root last.
 pub struct HuffmanTree<T> {
-    tree: Node<T>,
+    arena: Arena<T>,
     size: usize,
 }
 
@@ -24,61 +99,57 @@ impl<T> HuffmanTree<T>
 where
     T: Debug + Clone,
 {
-    pub fn from(tree: Node<T>, size: usize) -> Self {
-        Self { tree, size }
+    /// The arena must hold at least the root.
+    pub fn from(arena: Arena<T>, size: usize) -> Self {
+        assert!(!arena.is_empty(), "a tree needs a root");
+        Self { arena, size }
+    }
+
+    pub fn root(&self) -> NodeRef<'_, T> {
+        self.arena.root().expect("a tree has a root")
     }
 
     pub fn read(&self, codes: Codes) -> Vec<T> {
         let mut file = Vec::with_capacity(self.size);
-        let mut curr = &self.tree;
+        let root = self.root().index();
+        let mut curr = root;
 
         for code in codes {
-            if code {
-                if let Some(left) = curr.left_as_ref() {
-                    curr = left;
-                }
-            } else if let Some(right) = curr.right_as_ref() {
-                curr = right;
+            let node = self.arena.get(curr);
+            let next = if code { node.left() } else { node.right() };
+            if let Some(next) = next {
+                curr = next;
             }
 
-            if let Some(token) = curr.leaf() {
+            if let Some(token) = self.arena.get(curr).leaf() {
                 file.push(token.clone());
-                curr = &self.tree;
+                curr = root;
             }
         }
 
         file
     }
 
-    pub fn stream_codes(self) -> Vec<(T, Codes)>
-    where
-        T: Clone + Debug,
-    {
-        let mut array = vec![false; self.size];
-        codes_from(&self.tree, &mut array, 0)
-    }
-}
+    /// Every leaf with its code, left subtrees first. The stack holds at most one pending
+    /// right child per level, so memory is bounded by the depth rather than the input.
+    pub fn stream_codes(&self) -> Vec<(T, Code)> {
+        let mut codes = Vec::new();
+        let mut stack = vec![(self.root(), Code::default())];
 
-fn codes_from<T>(root: &Node<T>, array: &mut Vec<bool>, top: usize) -> Vec<(T, Codes)>
-where
-    T: Debug + Clone,
-{
-    let mut dict = Vec::new();
-    if let Some(left) = root.left_as_ref() {
-        array[top] = true;
-        dict.append(&mut codes_from(left, array, top + 1));
-    }
-
-    if let Some(right) = root.right_as_ref() {
-        array[top] = false;
-        dict.append(&mut codes_from(right, array, top + 1));
-    }
+        while let Some((node, code)) = stack.pop() {
+            if let Some(data) = node.leaf() {
+                codes.push((data.clone(), code));
+            }
+            if let Some(right) = node.right_as_ref() {
+                stack.push((right, code.push(false)));
+            }
+            if let Some(left) = node.left_as_ref() {
+                stack.push((left, code.push(true)));
+            }
+        }
 
-    if let Some(data) = root.leaf().take() {
-        dict.push((data.clone(), (array[..top].to_owned())))
+        codes
     }
-
-    dict
 }
 
 #[cfg(test)]
@@ -96,6 +167,7 @@ mod test {
             .unwrap()
             .stream_codes()
             .into_iter()
+            .map(|(t, code)| (t, code.to_codes()))
             .unzip();
 
         let expected_codes: Vec<Codes> = vec![
@@ -111,4 +183,46 @@ mod test {
         assert_eq!(expected_array, actual_array);
         assert_eq!(expected_codes, codes);
     }
+
+    #[test]
+    fn _with_codes() {
+        let codes = vec![vec![true], vec![false, true], vec![false, false]];
+        let tree = with_codes(b"abc", &codes, &[4, 2, 1], 0).unwrap();
+
+        assert_eq!(7, tree.root().freq());
+        assert_eq!(
+            b"abca".to_vec(),
+            tree.read(vec![true, false, true, false, false, true])
+        );
+        assert_eq!(
+            vec![
+                (b'a', Code { bits: 1, len: 1 }),
+                (b'b', Code { bits: 0b01, len: 2 }),
+                (b'c', Code { bits: 0b00, len: 2 })
+            ],
+            tree.stream_codes()
+        );
+    }
+
+    #[test]
+    fn deep_tree() {
+        // Doubling counts give a chain, the deepest tree for this many leaves.
+        let data: Vec<u8> = (0..64).collect();
+        let freqs: Vec<usize> = (0..64).map(|i| 1 << i).collect();
+        let tree = with_vecdeque(&data, &freqs, 0).unwrap();
+
+        let lengths: Vec<usize> = tree
+            .stream_codes()
+            .iter()
+            .map(|(_, code)| code.len)
+            .collect();
+        assert_eq!(63, *lengths.iter().max().unwrap());
+        assert_eq!(vec![63, 63], lengths[..2]);
+    }
+
+    #[test]
+    fn with_codes_rejects_prefixes() {
+        let codes = vec![vec![true], vec![true, false]];
+        assert!(with_codes(b"ab", &codes, &[1, 1], 0).is_none());
+    }
 }
@@ -1,57 +1,172 @@
-use super::node::Node;
+use super::node::{Arena, Node};
 
 use std::collections::VecDeque;
 use std::fmt::Debug;
 
+/// The queue a node was taken from: leaves in frequency order, or the branches merged so far.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Queue {
+    Leaves,
+    Branches,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Taken<T> {
+    Leaf(T),
+    /// The branch created by the merge at this index.
+    Branch(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pick<T> {
+    pub queue: Queue,
+    pub node: Taken<T>,
+    pub freq: usize,
+}
+
+/// One step of the construction: the two lowest nodes become children of a new branch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Merge<T> {
+    pub left: Option<Pick<T>>,
+    pub right: Option<Pick<T>>,
+    pub freq: usize,
+}
+
 fn min<T>(
-    first_queue: &mut VecDeque<Node<T>>,
-    second_queue: &mut VecDeque<Node<T>>,
-) -> Option<Node<T>> {
-    if first_queue.is_empty() {
-        return second_queue.pop_front();
-    }
+    arena: &Arena<T>,
+    first_queue: &mut VecDeque<usize>,
+    second_queue: &mut VecDeque<usize>,
+) -> Option<(Queue, usize)>
+where
+    T: Debug,
+{
+    let freq = |queue: &VecDeque<usize>| queue.front().map(|i| arena.get(*i).freq);
 
-    if second_queue.is_empty() {
-        return first_queue.pop_front();
+    match (freq(first_queue), freq(second_queue)) {
+        (None, _) => second_queue.pop_front().map(|n| (Queue::Branches, n)),
+        (Some(_), None) => first_queue.pop_front().map(|n| (Queue::Leaves, n)),
+        (Some(leaf), Some(branch)) if leaf < branch => {
+            first_queue.pop_front().map(|n| (Queue::Leaves, n))
+        }
+        _ => second_queue.pop_front().map(|n| (Queue::Branches, n)),
     }
+}
 
-    if first_queue.front() < second_queue.front() {
-        return first_queue.pop_front();
-    }
+pub fn from<T>(data: &[T], freq: &[usize]) -> Option<Arena<T>>
+where
+    T: Debug + Clone,
+{
+    build(data, freq, |_| {})
+}
+
+/// Like `from`, also returning every merge in the order it happened.
+pub fn traced<T>(data: &[T], freq: &[usize]) -> (Option<Arena<T>>, Vec<Merge<T>>)
+where
+    T: Debug + Clone,
+{
+    let mut merges = Vec::with_capacity(data.len());
+    let tree = build(data, freq, |merge| merges.push(merge));
 
-    second_queue.pop_front()
+    (tree, merges)
 }
 
-pub fn from<T>(data: &[T], freq: &[usize], size: usize) -> Option<Node<T>>
+/// The queues hold arena indices: leaves are added first, in frequency order, and every merge
+/// appends its branch, so the root ends up last.
+fn build<T, F>(data: &[T], freq: &[usize], mut on_merge: F) -> Option<Arena<T>>
 where
     T: Debug + Clone,
+    F: FnMut(Merge<T>),
 {
-    let mut first_queue = VecDeque::with_capacity(size);
-    let mut second_queue = VecDeque::with_capacity(size);
+    let mut arena = Arena::with_capacity(data.len());
+    let mut first_queue = VecDeque::with_capacity(data.len());
+    let mut second_queue = VecDeque::with_capacity(data.len());
+    // Branches leave the second queue in the order they were merged.
+    let mut branches_taken = 0;
 
     data.iter().zip(freq.iter()).for_each(|(d, f)| {
-        first_queue.push_back(Node::new_leaf(d.clone(), *f));
+        first_queue.push_back(arena.push(Node::new_leaf(d.clone(), *f)));
     });
 
     while !(first_queue.is_empty() && second_queue.len() == 1) {
-        let mut left = min(&mut first_queue, &mut second_queue);
-        let mut right = min(&mut first_queue, &mut second_queue);
+        let left = min(&arena, &mut first_queue, &mut second_queue);
+        let right = min(&arena, &mut first_queue, &mut second_queue);
 
-        let get_freq: fn(&Node<T>) -> usize = |n| n.freq;
-        let top_freq = left.as_ref().map(get_freq).unwrap_or_default()
-            + right.as_ref().map(get_freq).unwrap_or_default();
-        let mut top = Node::new_branch(top_freq);
+        let mut pick = |(queue, index): &(Queue, usize)| {
+            let node = arena.get(*index);
+            let taken = match node.leaf() {
+                Some(t) => Taken::Leaf(t.clone()),
+                None => {
+                    branches_taken += 1;
+                    Taken::Branch(branches_taken - 1)
+                }
+            };
+            Pick {
+                queue: *queue,
+                node: taken,
+                freq: node.freq,
+            }
+        };
+        let left_pick = left.as_ref().map(&mut pick);
+        let right_pick = right.as_ref().map(&mut pick);
 
-        if let Some(left) = left.take() {
-            top.left(Box::new(left));
-        }
+        let top_freq = left_pick.as_ref().map(|p| p.freq).unwrap_or_default()
+            + right_pick.as_ref().map(|p| p.freq).unwrap_or_default();
+        let top = Node::new_branch(top_freq, left.map(|(_, i)| i), right.map(|(_, i)| i));
 
-        if let Some(right) = right.take() {
-            top.right(Box::new(right));
-        }
+        on_merge(Merge {
+            left: left_pick,
+            right: right_pick,
+            freq: top_freq,
+        });
+        second_queue.push_back(arena.push(top));
+    }
+
+    Some(arena)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
 
-        second_queue.push_back(top);
+    #[test]
+    fn arena_holds_every_node() {
+        let arena = from(b"bcad", &[1, 2, 4, 8]).unwrap();
+        let root = arena.root().unwrap();
+
+        assert_eq!(2 * 4 - 1, root.index() + 1);
+        assert_eq!(15, root.freq());
+        assert_eq!(Some(&b'd'), root.right_as_ref().and_then(|n| n.leaf()));
     }
 
-    second_queue.pop_front()
+    #[test]
+    fn traced_merges() {
+        let (_, merges) = traced(b"bca", &[1, 2, 4]);
+
+        let leaf = |t, freq| {
+            Some(Pick {
+                queue: Queue::Leaves,
+                node: Taken::Leaf(t),
+                freq,
+            })
+        };
+        assert_eq!(
+            vec![
+                Merge {
+                    left: leaf(b'b', 1),
+                    right: leaf(b'c', 2),
+                    freq: 3,
+                },
+                Merge {
+                    left: Some(Pick {
+                        queue: Queue::Branches,
+                        node: Taken::Branch(0),
+                        freq: 3,
+                    }),
+                    right: leaf(b'a', 4),
+                    freq: 7,
+                },
+            ],
+            merges
+        );
+    }
 }
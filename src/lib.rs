@@ -0,0 +1,23 @@
+#[macro_use]
+extern crate prettytable;
+
+pub mod ans;
+pub mod armor;
+pub mod bench;
+pub mod blocks;
+pub mod bytes;
+pub mod code_lengths;
+pub mod compress;
+pub mod crypt;
+pub mod diff;
+pub mod error;
+pub mod explain;
+pub mod explore;
+pub mod export;
+pub mod format;
+pub mod histogram;
+pub mod huffman_tree;
+pub mod parity;
+pub mod range_coder;
+pub mod stats;
+pub mod stream;
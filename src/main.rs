@@ -1,86 +1,773 @@
 #[macro_use]
 extern crate clap;
-#[macro_use]
-extern crate prettytable;
 
-mod bytes;
 mod cli;
-mod compress;
-mod format;
-mod huffman_tree;
-mod stats;
 
 use clap::ArgMatches;
 
+use std::cell::OnceCell;
 use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::{
     io,
     io::{Read, Write},
+    process,
 };
 
-use compress::{decode, encode};
+use huff::armor::{self, Encoding};
+use huff::compress::{
+    build_tree, decode, encode_huffman, encode_mode, encode_sampled, read_tree, Mode,
+};
+use huff::format::{read_frame, read_sizes, Method, MAGIC};
+use huff::huffman_tree::strategy::Strategy;
+use huff::huffman_tree::tree::HuffmanTree;
+use huff::{bench, blocks, crypt, diff, explain, explore, export, parity, stats, stream};
 
-const FILE_READ_FAILED: &str = "Problem reading from source";
+const SUFFIX: &str = ".huff";
 
 fn main() {
     let matches = cli::app();
+    let passphrase = Passphrase::from(&matches);
 
-    let mut filename = filename(&matches);
-    let command = command(&matches);
-
-    let output = match command {
+    match command(&matches) {
         Command::Process => {
-            let source = source(&matches).expect(FILE_READ_FAILED);
-            if matches.is_present("decode") {
-                filename = filename.map(|name| name.replace(".huff", ""));
-                Some(decode(&source))
+            if !process(&matches) {
+                process::exit(1);
+            }
+        }
+        Command::Stats(file, format) => {
+            let name = file.as_deref().unwrap_or("stdin");
+            let source = read(file.as_deref())
+                .and_then(|source| open(source, name, &passphrase))
+                .unwrap_or_else(|err| exit(&err));
+            stats::print(&source, format).unwrap_or_else(|err| exit(&err));
+        }
+        Command::Diff(a, b, format) => {
+            let [a, b] = [&a, &b].map(|file| {
+                fs::read(file)
+                    .and_then(|source| open(source, file, &passphrase))
+                    .unwrap_or_else(|err| exit(&err))
+            });
+            diff::print(&a, &b, format).unwrap_or_else(|err| exit(&err));
+        }
+        Command::Tree(file, format) => {
+            let name = file.as_deref().unwrap_or("stdin");
+            let source = read(file.as_deref())
+                .and_then(|source| open(source, name, &passphrase))
+                .unwrap_or_else(|err| exit(&err));
+            let tree = tree(&source).unwrap_or_else(|err| exit(&err));
+            print!("{}", export::export(&tree, format));
+        }
+        Command::Bench(files, modes, strategies, iterations) => {
+            let mut measurements = Vec::with_capacity(files.len() * modes.len() * strategies.len());
+            for file in &files {
+                let source = fs::read(file).unwrap_or_else(|err| exit(&err));
+                for mode in &modes {
+                    // Strategies only differ for huffman coding.
+                    let strategies = match mode {
+                        Mode::Huffman => &strategies[..],
+                        Mode::Ans | Mode::Range => &strategies[..1],
+                    };
+                    for strategy in strategies {
+                        let measurement =
+                            bench::measure(file, &source, *mode, *strategy, iterations)
+                                .unwrap_or_else(|err| exit(&err));
+                        measurements.push(measurement);
+                    }
+                }
+            }
+            bench::print(&measurements);
+        }
+        Command::Explain(file, bytes, json) => {
+            let source = read(file.as_deref()).unwrap_or_else(|err| exit(&err));
+            let explanation = explain::explain(&source, bytes);
+            if json {
+                println!("{}", explain::to_json(&explanation));
             } else {
-                filename = filename.map(|name| format!("{}.huff", name));
-                Some(encode(&source))
+                print!("{}", explain::to_text(&explanation));
             }
         }
-        Command::Stats(file) => {
-            let source = fs::read(file).expect(FILE_READ_FAILED);
-            stats::print(&source);
-            None
+        Command::Explore(file) => {
+            let source = fs::read(&file)
+                .and_then(|source| open(source, &file, &passphrase))
+                .unwrap_or_else(|err| exit(&err));
+            let source = huffman_coded(source).unwrap_or_else(|err| exit(&err));
+            explore::run(&source).unwrap_or_else(|err| exit(&err));
         }
-    };
+        Command::Test(files) => {
+            if !test(&files, &passphrase) {
+                process::exit(1);
+            }
+        }
+        Command::Recover(file, output, stdout, force) => {
+            let complete = recover(Path::new(&file), output, stdout, force, &passphrase)
+                .unwrap_or_else(|err| exit(&err));
+            if !complete {
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn exit(err: &dyn std::error::Error) -> ! {
+    eprintln!("huff: {}", err);
+    process::exit(1)
+}
+
+/// The tree of a huffman coded file, or the tree huffman coding would build for any other data.
+fn tree(source: &[u8]) -> huff::error::Result<HuffmanTree<u8>> {
+    if source.starts_with(MAGIC) {
+        match read_frame(source)?.method {
+            Method::Huffman | Method::Canonical => return read_tree(source),
+            Method::Stored | Method::Ans | Method::Range | Method::Blocks => {
+                return uncoded_tree(&decode(source)?)
+            }
+        }
+    }
 
-    if let Some(output) = output {
-        if matches.is_present("stats") {
-            stats::print(&output);
-        } else if let Some(destination_file) = filename {
-            fs::write(destination_file, output).unwrap();
-        } else {
-            io::stdout().write_all(&output).unwrap();
+    uncoded_tree(source)
+}
+
+fn uncoded_tree(data: &[u8]) -> huff::error::Result<HuffmanTree<u8>> {
+    build_tree(data).ok_or(huff::error::Error::InvalidDictionary)
+}
+
+/// Huffman codes anything that is not already, so it can be explored.
+fn huffman_coded(source: Vec<u8>) -> huff::error::Result<Vec<u8>> {
+    if source.starts_with(MAGIC) {
+        match read_frame(&source)?.method {
+            Method::Huffman | Method::Canonical => return Ok(source),
+            Method::Stored | Method::Ans | Method::Range | Method::Blocks => {
+                return Ok(encode_huffman(&decode(&source)?))
+            }
         }
     }
+
+    Ok(encode_huffman(&source))
+}
+
+/// Processes every input, reporting failures as they happen. Returns false when any input failed.
+fn process(matches: &ArgMatches) -> bool {
+    let options = Options::from(matches);
+    let files = files(matches);
+
+    if files.is_empty() {
+        return report("stdin", process_stdin(&options));
+    }
+
+    if options.output.is_some() && files.len() > 1 {
+        eprintln!("huff: --output cannot be used with multiple input files");
+        return false;
+    }
+
+    let failures = files
+        .iter()
+        .filter(|file| !report(file, process_file(Path::new(file), &options)))
+        .count();
+
+    failures == 0
+}
+
+fn report(name: &str, result: io::Result<()>) -> bool {
+    if let Err(err) = &result {
+        eprintln!("huff: {}: {}", name, err);
+    }
+
+    result.is_ok()
+}
+
+/// Verifies every file, printing OK or FAIL for each. Returns false when any file failed.
+fn test(files: &[String], passphrase: &Passphrase) -> bool {
+    let check = |name, source| open(source, name, passphrase).and_then(verify);
+    if files.is_empty() {
+        return report_test(
+            "stdin",
+            read(None).and_then(|source| check("stdin", source)),
+        );
+    }
+
+    let failures = files
+        .iter()
+        .filter(|file| !report_test(file, fs::read(file).and_then(|source| check(file, source))))
+        .count();
+
+    failures == 0
 }
 
-fn source(matches: &ArgMatches) -> io::Result<Vec<u8>> {
-    if let Some(input_file) = matches.value_of("filepath") {
-        return fs::read(input_file);
+fn report_test(name: &str, result: io::Result<bool>) -> bool {
+    match &result {
+        Ok(true) => println!("{}: OK", name),
+        Ok(false) => println!("{}: OK (no checksum)", name),
+        Err(err) => println!("{}: FAIL ({})", name, err),
+    }
+
+    result.is_ok()
+}
+
+/// Decodes the source in memory. Returns whether a checksum was available to check against.
+fn verify(source: Vec<u8>) -> io::Result<bool> {
+    let frame = read_frame(&source).map_err(invalid_data)?;
+    if matches!(frame.method, Method::Huffman | Method::Canonical) {
+        read_sizes(&source).map_err(invalid_data)?;
+    }
+    decode(&source).map_err(invalid_data)?;
+
+    Ok(frame.trailer.is_some())
+}
+
+/// Writes what can be recovered of the file and reports the ranges lost on stderr. Returns
+/// whether nothing was lost.
+fn recover(
+    input: &Path,
+    output: Option<PathBuf>,
+    stdout: bool,
+    force: bool,
+    passphrase: &Passphrase,
+) -> io::Result<bool> {
+    // Damage the parity cannot repair is left for the blocks to skip.
+    let name = input.to_string_lossy();
+    let source = repair(dearmor(fs::read(input)?)?, &name, true)?;
+    let source = open(source, &name, passphrase)?;
+    let recovery = blocks::recover(&source).map_err(invalid_data)?;
+
+    if stdout {
+        io::stdout().write_all(&recovery.data)?;
+    } else {
+        let destination = match output {
+            Some(destination) => destination,
+            None => destination(input, true)?,
+        };
+        check_destination(Some(input), &destination, force)?;
+        fs::write(&destination, &recovery.data)?;
+    }
+
+    let lost = recovery.lost.iter().map(|range| range.len()).sum::<usize>();
+    eprintln!(
+        "huff: {}: recovered {} of {} bytes",
+        input.display(),
+        recovery.total - lost,
+        recovery.total
+    );
+    for range in &recovery.lost {
+        eprintln!(
+            "  lost bytes {}..{} ({} bytes)",
+            range.start,
+            range.end,
+            range.len()
+        );
+    }
+
+    Ok(recovery.lost.is_empty())
+}
+
+/// Reads the whole file, or stdin when no file is given.
+fn read(file: Option<&str>) -> io::Result<Vec<u8>> {
+    if let Some(file) = file {
+        return fs::read(file);
     }
 
     let mut source = Vec::new();
-    let mut stdin = io::stdin();
-    stdin.read_to_end(&mut source)?;
+    io::stdin().read_to_end(&mut source)?;
     Ok(source)
 }
 
-fn filename(matches: &ArgMatches) -> Option<String> {
-    matches.value_of("filepath").map(|f| f.to_owned())
+/// The compressed file inside armored text, parity and encryption, or the source as it is
+/// when it has none of them.
+fn open(source: Vec<u8>, name: &str, passphrase: &Passphrase) -> io::Result<Vec<u8>> {
+    let source = repair(dearmor(source)?, name, false)?;
+    if !crypt::is_encrypted(&source) {
+        return Ok(source);
+    }
+
+    crypt::decrypt(&source, passphrase.get(false)?).map_err(invalid_data)
+}
+
+fn dearmor(source: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !armor::is_armored(&source) {
+        return Ok(source);
+    }
+
+    armor::dearmor(&source).map_err(invalid_data)
+}
+
+/// Strips the parity, reporting on stderr what it repaired. Damage it cannot repair fails,
+/// unless `salvage` is set and it is only reported.
+fn repair(source: Vec<u8>, name: &str, salvage: bool) -> io::Result<Vec<u8>> {
+    let repair = match parity::repair(&source) {
+        Some(repair) => repair,
+        None => return Ok(source),
+    };
+
+    if !repair.repaired.is_empty() {
+        eprintln!(
+            "huff: {}: repaired {} bytes of the compressed file at {}",
+            name,
+            repair
+                .repaired
+                .iter()
+                .map(|range| range.len())
+                .sum::<usize>(),
+            ranges(&repair.repaired)
+        );
+    }
+    if !repair.damaged.is_empty() {
+        let message = format!(
+            "too damaged for the parity to repair bytes {} of the compressed file",
+            ranges(&repair.damaged)
+        );
+        if !salvage {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        eprintln!("huff: {}: {}", name, message);
+    }
+
+    Ok(repair.data)
+}
+
+/// The first few ranges, and how many more there are.
+fn ranges(ranges: &[Range<usize>]) -> String {
+    const SHOWN: usize = 5;
+    let mut text = ranges
+        .iter()
+        .take(SHOWN)
+        .map(|range| format!("{}..{}", range.start, range.end))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if ranges.len() > SHOWN {
+        text += &format!(" and {} more", ranges.len() - SHOWN);
+    }
+
+    text
+}
+
+fn process_stdin(options: &Options) -> io::Result<()> {
+    let source = read(None)?;
+
+    let output = transform(&source, "stdin", options)?;
+    match (&output, &options.output) {
+        (None, _) => Ok(()),
+        (Some(output), Some(destination)) => write_file(None, destination, output, options),
+        (Some(output), None) => io::stdout().write_all(output),
+    }
+}
+
+fn process_file(input: &Path, options: &Options) -> io::Result<()> {
+    if options.streams() && input.metadata()?.is_file() {
+        return stream_file(input, options);
+    }
+
+    let source = fs::read(input)?;
+    let output = match transform(&source, &input.to_string_lossy(), options)? {
+        Some(output) => output,
+        None => return Ok(()),
+    };
+
+    if options.stdout {
+        return io::stdout().write_all(&output);
+    }
+
+    let destination = match &options.output {
+        Some(destination) => destination.clone(),
+        None => destination(input, options.decode)?,
+    };
+    write_file(Some(input), &destination, &output, options)?;
+
+    if !options.keep {
+        fs::remove_file(input)?;
+    }
+
+    Ok(())
+}
+
+/// Compresses a regular file in two passes rather than reading it into memory.
+fn stream_file(input: &Path, options: &Options) -> io::Result<()> {
+    let mut source = fs::File::open(input)?;
+
+    if options.stdout {
+        let mut stdout = io::BufWriter::new(io::stdout().lock());
+        stream::encode(&mut source, &mut stdout, options.strategy)?;
+        return stdout.flush();
+    }
+
+    let destination = match &options.output {
+        Some(destination) => destination.clone(),
+        None => destination(input, options.decode)?,
+    };
+    check_destination(Some(input), &destination, options.force)?;
+
+    let mut output = io::BufWriter::new(fs::File::create(&destination)?);
+    let written = match options.sample {
+        Some(percent) => {
+            stream::encode_sampled(&mut source, &mut output, options.strategy, percent)
+        }
+        None => stream::encode(&mut source, &mut output, options.strategy),
+    }
+    .and_then(|_| output.flush());
+    if let Err(err) = written {
+        drop(output);
+        let _ = fs::remove_file(&destination);
+        return Err(err);
+    }
+
+    if !options.keep {
+        fs::remove_file(input)?;
+    }
+
+    Ok(())
+}
+
+/// (De)compresses the source, or prints its stats when `--stats` is set.
+fn transform(source: &[u8], name: &str, options: &Options) -> io::Result<Option<Vec<u8>>> {
+    if options.decode {
+        let source = open(source.to_vec(), name, &options.passphrase)?;
+        if options.stats {
+            return stats::print(&source, options.format)
+                .map(|_| None)
+                .map_err(invalid_data);
+        }
+        return decode(&source).map(Some).map_err(invalid_data);
+    }
+
+    if options.stats {
+        let result = match options.sample {
+            Some(percent) => stats::print_sampled(source, percent, options.format),
+            None => stats::print(&encode_huffman(source), options.format),
+        };
+        return result.map(|_| None).map_err(invalid_data);
+    }
+
+    let encode = |source: &[u8]| match (options.mode, options.sample) {
+        (Mode::Huffman, Some(percent)) => encode_sampled(source, options.strategy, percent),
+        (mode, _) => encode_mode(source, mode, options.strategy),
+    };
+    let output = match options.block_size {
+        Some(size) => blocks::encode(source, size, encode),
+        None => encode(source),
+    };
+    let output = if options.encrypt {
+        crypt::encrypt(&output, options.passphrase.get(true)?).map_err(invalid_data)?
+    } else {
+        output
+    };
+    // Parity goes over the bytes as stored, so it can repair them even when encrypted.
+    let output = match options.parity {
+        Some(percent) => parity::encode(&output, percent),
+        None => output,
+    };
+    Ok(Some(match options.armor {
+        Some(encoding) => armor::armor(&output, encoding).into_bytes(),
+        None => output,
+    }))
+}
+
+fn destination(input: &Path, decode: bool) -> io::Result<PathBuf> {
+    let name = input.to_string_lossy();
+
+    if !decode {
+        return Ok(PathBuf::from(format!("{}{}", name, SUFFIX)));
+    }
+
+    match name.strip_suffix(SUFFIX) {
+        Some(stripped) if !stripped.is_empty() => Ok(PathBuf::from(stripped)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unknown suffix, expected {}", SUFFIX),
+        )),
+    }
+}
+
+fn write_file(
+    input: Option<&Path>,
+    destination: &Path,
+    output: &[u8],
+    options: &Options,
+) -> io::Result<()> {
+    check_destination(input, destination, options.force)?;
+    fs::write(destination, output)
+}
+
+/// Checked before anything is written. Writing over the input would lose it, and removing the
+/// input would then lose the output too.
+fn check_destination(input: Option<&Path>, destination: &Path, force: bool) -> io::Result<()> {
+    if let (Some(Ok(input)), Ok(destination)) =
+        (input.map(fs::canonicalize), fs::canonicalize(destination))
+    {
+        if input == destination {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is the input file", destination.display()),
+            ));
+        }
+    }
+
+    if !force && destination.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already exists; use --force to overwrite",
+                destination.display()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+fn invalid_data(err: huff::error::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn files(matches: &ArgMatches) -> Vec<String> {
+    matches
+        .values_of("files")
+        .into_iter()
+        .flatten()
+        .chain(matches.value_of("filepath"))
+        .map(|f| f.to_owned())
+        .collect()
 }
 
 fn command(matches: &ArgMatches) -> Command {
     match matches.subcommand() {
         ("", None) => Command::Process,
-        ("stats", Some(file)) => Command::Stats(file.value_of("file").unwrap().to_string()),
+        ("stats", Some(stats)) => {
+            Command::Stats(stats.value_of("file").map(|f| f.to_owned()), format(stats))
+        }
+        ("diff", Some(diff)) => Command::Diff(
+            diff.value_of("a").unwrap().to_owned(),
+            diff.value_of("b").unwrap().to_owned(),
+            format(diff),
+        ),
+        ("tree", Some(tree)) => Command::Tree(
+            tree.value_of("file").map(|f| f.to_owned()),
+            value_t!(tree, "format", export::Format).unwrap_or_else(|err| err.exit()),
+        ),
+        ("bench", Some(bench)) => Command::Bench(
+            bench
+                .values_of("files")
+                .into_iter()
+                .flatten()
+                .map(|f| f.to_owned())
+                .collect(),
+            values_t!(bench, "mode", Mode).unwrap_or_else(|err| err.exit()),
+            values_t!(bench, "strategy", Strategy).unwrap_or_else(|err| err.exit()),
+            value_t!(bench, "iterations", usize).unwrap_or_else(|err| err.exit()),
+        ),
+        ("explain", Some(explain)) => Command::Explain(
+            explain.value_of("file").map(|f| f.to_owned()),
+            value_t!(explain, "bytes", usize).unwrap_or_else(|err| err.exit()),
+            explain.value_of("format") == Some("json"),
+        ),
+        ("explore", Some(explore)) => {
+            Command::Explore(explore.value_of("file").unwrap().to_owned())
+        }
+        ("test", Some(test)) => Command::Test(
+            test.values_of("files")
+                .into_iter()
+                .flatten()
+                .map(|f| f.to_owned())
+                .collect(),
+        ),
+        ("recover", Some(recover)) => Command::Recover(
+            recover.value_of("file").unwrap().to_owned(),
+            recover.value_of("output").map(PathBuf::from),
+            recover.is_present("stdout"),
+            recover.is_present("force"),
+        ),
         _ => unreachable!(),
     }
 }
 
+fn format(matches: &ArgMatches) -> stats::Format {
+    value_t!(matches, "format", stats::Format).unwrap_or_else(|err| err.exit())
+}
+
 enum Command {
     Process,
-    Stats(String),
+    Stats(Option<String>, stats::Format),
+    Diff(String, String, stats::Format),
+    Tree(Option<String>, export::Format),
+    Bench(Vec<String>, Vec<Mode>, Vec<Strategy>, usize),
+    Explain(Option<String>, usize, bool),
+    Explore(String),
+    Test(Vec<String>),
+    Recover(String, Option<PathBuf>, bool, bool),
+}
+
+struct Options {
+    decode: bool,
+    stats: bool,
+    stdout: bool,
+    keep: bool,
+    force: bool,
+    output: Option<PathBuf>,
+    format: stats::Format,
+    mode: Mode,
+    strategy: Strategy,
+    sample: Option<u8>,
+    armor: Option<Encoding>,
+    block_size: Option<usize>,
+    parity: Option<u8>,
+    encrypt: bool,
+    passphrase: Passphrase,
+}
+
+impl From<&ArgMatches<'_>> for Options {
+    fn from(matches: &ArgMatches) -> Self {
+        let stdout = matches.is_present("stdout");
+
+        Self {
+            decode: matches.is_present("decode"),
+            stats: matches.is_present("stats"),
+            stdout,
+            keep: stdout || matches.is_present("keep"),
+            force: matches.is_present("force"),
+            output: matches.value_of("output").map(PathBuf::from),
+            format: format(matches),
+            mode: value_t!(matches, "mode", Mode).unwrap_or_else(|err| err.exit()),
+            strategy: value_t!(matches, "strategy", Strategy).unwrap_or_else(|err| err.exit()),
+            sample: matches
+                .value_of("sample")
+                .map(|_| value_t!(matches, "sample", u8).unwrap_or_else(|err| err.exit())),
+            // A bare --armor has no value and means the default encoding.
+            armor: matches
+                .is_present("armor")
+                .then(|| value_t!(matches, "armor", Encoding).unwrap_or_default()),
+            block_size: matches.value_of("block-size").and_then(cli::parse_size),
+            parity: matches
+                .value_of("parity")
+                .map(|_| value_t!(matches, "parity", u8).unwrap_or_else(|err| err.exit())),
+            encrypt: matches.is_present("encrypt"),
+            passphrase: Passphrase::from(matches),
+        }
+    }
+}
+
+/// The passphrase to encrypt or decrypt with, asked for once however many files need it.
+struct Passphrase {
+    file: Option<PathBuf>,
+    read: OnceCell<Vec<u8>>,
+}
+
+impl Passphrase {
+    /// Reads the passphrase file, or asks on the terminal, twice when `confirm` is set.
+    fn get(&self, confirm: bool) -> io::Result<&[u8]> {
+        if let Some(passphrase) = self.read.get() {
+            return Ok(passphrase);
+        }
+
+        let passphrase = match &self.file {
+            Some(file) => {
+                let text = fs::read(file)?;
+                let line = text.split(|b| *b == b'\n').next().unwrap_or_default();
+                line.strip_suffix(b"\r").unwrap_or(line).to_vec()
+            }
+            None => {
+                let passphrase = prompt("Passphrase: ")?;
+                if confirm && prompt("Repeat passphrase: ")? != passphrase {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "passphrases do not match",
+                    ));
+                }
+                passphrase.into_bytes()
+            }
+        };
+        if passphrase.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty passphrase",
+            ));
+        }
+
+        Ok(self.read.get_or_init(|| passphrase))
+    }
+}
+
+fn prompt(prompt: &str) -> io::Result<String> {
+    rpassword::prompt_password(prompt).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!(
+                "cannot ask for the passphrase ({}), use --passphrase-file",
+                err
+            ),
+        )
+    })
+}
+
+impl From<&ArgMatches<'_>> for Passphrase {
+    fn from(matches: &ArgMatches) -> Self {
+        // The option is global, so it may be given to a subcommand instead.
+        let file = matches
+            .value_of("passphrase-file")
+            .or_else(|| matches.subcommand().1?.value_of("passphrase-file"));
+
+        Self {
+            file: file.map(PathBuf::from),
+            read: OnceCell::new(),
+        }
+    }
+}
+
+impl Options {
+    /// Whether a regular file is compressed by `stream_file` rather than in memory. Sampling
+    /// overwrites the payload size once it is known, which stdout cannot do.
+    fn streams(&self) -> bool {
+        let sampled_to_stdout = self.stdout && self.sample.is_some();
+        self.mode == Mode::Huffman
+            && self.armor.is_none()
+            && !self.encrypt
+            && self.block_size.is_none()
+            && self.parity.is_none()
+            && !self.decode
+            && !self.stats
+            && !sampled_to_stdout
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use huff::compress::encode;
+
+    fn options(args: &[&str]) -> Options {
+        let args = ["huff"].iter().chain(args);
+        Options::from(&cli::definition().get_matches_from(args))
+    }
+
+    #[test]
+    fn output_over_the_input_is_refused() {
+        let path = std::env::temp_dir().join(format!("huff-same-file-{}", process::id()));
+        let name = path.to_str().unwrap();
+        let source = b"geeksforgeeks".repeat(20);
+
+        fs::write(&path, &source).unwrap();
+        let streamed = process_file(&path, &options(&["--force", "-o", name, name]));
+        assert_eq!(io::ErrorKind::InvalidInput, streamed.unwrap_err().kind());
+        assert_eq!(source, fs::read(&path).unwrap());
+
+        fs::write(&path, encode(&source)).unwrap();
+        let decoded = process_file(&path, &options(&["-d", "--force", "-o", name, name]));
+        assert_eq!(io::ErrorKind::InvalidInput, decoded.unwrap_err().kind());
+        assert_eq!(encode(&source), fs::read(&path).unwrap());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sampling_to_stdout_is_not_streamed() {
+        let sampled = options(&["-c", "--sample", "10", "file"]);
+        let unsampled = options(&["-c", "file"]);
+        assert!(!sampled.streams());
+        assert!(unsampled.streams());
+        assert!(options(&["--sample", "10", "file"]).streams());
+
+        let source = b"geeksforgeeks".repeat(1000);
+        assert_ne!(
+            transform(&source, "file", &sampled).unwrap(),
+            transform(&source, "file", &unsampled).unwrap()
+        );
+    }
 }
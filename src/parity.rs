@@ -0,0 +1,359 @@
+//! Reed-Solomon parity over a compressed file, so corrupted bytes are repaired rather than only
+//! detected. The file is cut into shards of equal size, grouped in stripes of up to
+//! `DATA_SHARDS`. Each stripe gets parity shards worth a percentage of its data shards and a
+//! CRC-32 of every shard, which tells the decoder which shards to rebuild. As many shards as
+//! there are parity shards can be rebuilt in each stripe.
+//!
+//! ```text
+//! header | stripe | stripe | ... | header
+//! stripe: CRCs | data shards | parity shards | CRCs
+//! header: MAGIC | version | percent | shard size u32 | length u64 | CRC-32 of the header
+//! ```
+//!
+//! The header and the CRCs are written twice, and either copy will do.
+
+use std::ops::Range;
+
+use reed_solomon_erasure::galois_8::ReedSolomon;
+
+use crate::bytes::{read_be_u32, read_be_u8, read_be_usize};
+
+pub const MAGIC: &[u8] = b"HUFPAR";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 6 + 1 + 1 + 4 + 8 + 4;
+/// The most data shards in a stripe. Parity of up to 100% keeps a stripe within the 256 shards
+/// the code allows.
+const DATA_SHARDS: usize = 64;
+/// Shards are as small as this unless the file is small, so a burst of damage like a bad sector
+/// costs few shards.
+const MAX_SHARD_SIZE: usize = 4096;
+/// Smaller shards would spend more on their CRCs than they hold.
+const MIN_SHARD_SIZE: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Header {
+    percent: u8,
+    shard_size: usize,
+    length: usize,
+}
+
+impl Header {
+    fn write(&self) -> Vec<u8> {
+        let fields = [
+            MAGIC,
+            &[VERSION, self.percent],
+            &(self.shard_size as u32).to_be_bytes(),
+            &(self.length as u64).to_be_bytes(),
+        ]
+        .concat();
+        let checksum = crc32fast::hash(&fields).to_be_bytes();
+
+        [fields, checksum.to_vec()].concat()
+    }
+
+    /// The header at the start of the input, if its checksum holds and it is one this version
+    /// writes.
+    fn read(input: &[u8]) -> Option<Self> {
+        let fields = input.strip_prefix(MAGIC)?;
+        let (version, remaining) = read_be_u8(fields)?;
+        let (percent, remaining) = read_be_u8(remaining)?;
+        let (shard_size, remaining) = read_be_u32(remaining)?;
+        let (length, remaining) = read_be_usize(remaining)?;
+        let (checksum, _) = read_be_u32(remaining)?;
+        if crc32fast::hash(&input[..HEADER_SIZE - 4]) != checksum {
+            return None;
+        }
+
+        let shard_size = shard_size as usize;
+        let valid = version == VERSION as usize
+            && (1..=100).contains(&percent)
+            && (MIN_SHARD_SIZE..=MAX_SHARD_SIZE).contains(&shard_size);
+        valid.then_some(Self {
+            percent: percent as u8,
+            shard_size,
+            length,
+        })
+    }
+
+    /// The number of data and parity shards of every stripe.
+    fn stripes(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let shards = self.length.div_ceil(self.shard_size);
+        (0..shards.div_ceil(DATA_SHARDS)).map(move |stripe| {
+            let data = (shards - stripe * DATA_SHARDS).min(DATA_SHARDS);
+            (data, (data * self.percent as usize).div_ceil(100))
+        })
+    }
+}
+
+/// Whether the source starts or ends with an intact parity header.
+pub fn is_protected(source: &[u8]) -> bool {
+    header(source).is_some()
+}
+
+fn header(source: &[u8]) -> Option<Header> {
+    let end = source.len().checked_sub(HEADER_SIZE)?;
+    Header::read(source).or_else(|| Header::read(&source[end..]))
+}
+
+/// Appends parity shards worth `percent` of the data.
+pub fn encode(data: &[u8], percent: u8) -> Vec<u8> {
+    assert!((1..=100).contains(&percent));
+
+    let shard_size = data
+        .len()
+        .div_ceil(DATA_SHARDS)
+        .clamp(MIN_SHARD_SIZE, MAX_SHARD_SIZE);
+    let header = Header {
+        percent,
+        shard_size,
+        length: data.len(),
+    };
+
+    let mut output = header.write();
+    let mut shards = data.chunks(shard_size);
+    for (data_shards, parity_shards) in header.stripes() {
+        let mut stripe: Vec<Vec<u8>> = shards
+            .by_ref()
+            .take(data_shards)
+            .map(|shard| [shard, &vec![0; shard_size - shard.len()]].concat())
+            .collect();
+        stripe.resize(data_shards + parity_shards, vec![0; shard_size]);
+        ReedSolomon::new(data_shards, parity_shards)
+            .and_then(|code| code.encode(&mut stripe))
+            .expect("a stripe has at most 256 shards of equal size");
+
+        let checksums: Vec<u8> = stripe
+            .iter()
+            .flat_map(|shard| crc32fast::hash(shard).to_be_bytes())
+            .collect();
+        output.extend_from_slice(&checksums);
+        stripe
+            .iter()
+            .for_each(|shard| output.extend_from_slice(shard));
+        output.extend_from_slice(&checksums);
+    }
+    output.extend(header.write());
+
+    output
+}
+
+/// The data of a protected file, with every shard that failed its CRC rebuilt where the stripe
+/// has parity enough.
+#[derive(Debug, PartialEq)]
+pub struct Repair {
+    pub data: Vec<u8>,
+    /// Ranges of the data that were damaged and have been repaired, in order.
+    pub repaired: Vec<Range<usize>>,
+    /// Ranges of the data in stripes with more damaged shards than parity, left as they were
+    /// read. Zeros where the file was cut short, and the data stops at the last stripe that
+    /// starts in the file.
+    pub damaged: Vec<Range<usize>>,
+}
+
+/// Repairs what the parity allows, or `None` when the source has no intact header.
+pub fn repair(source: &[u8]) -> Option<Repair> {
+    let header = header(source)?;
+    let size = header.shard_size;
+
+    let mut data = Vec::with_capacity(header.length.min(source.len()));
+    let mut repaired = Vec::new();
+    let mut damaged = Vec::new();
+    let mut position = HEADER_SIZE;
+    for (data_shards, parity_shards) in header.stripes() {
+        // A forged length can claim stripes far past the end; they are all damaged.
+        if position >= source.len() {
+            break;
+        }
+        let count = data_shards + parity_shards;
+        let checksums = |at: usize| source.get(at..at + 4 * count);
+        let first = checksums(position);
+        let second = checksums(position + 4 * count + count * size);
+        let intact = |i: usize, shard: &[u8]| {
+            let checksum = crc32fast::hash(shard).to_be_bytes();
+            [first, second]
+                .iter()
+                .flatten()
+                .any(|checksums| checksums[4 * i..4 * i + 4] == checksum)
+        };
+
+        let start = position + 4 * count;
+        let read: Vec<&[u8]> = (0..count)
+            .map(|i| {
+                let at = (start + i * size).min(source.len());
+                &source[at..(at + size).min(source.len())]
+            })
+            .collect();
+        let mut shards: Vec<Option<Vec<u8>>> = read
+            .iter()
+            .enumerate()
+            .map(|(i, shard)| (shard.len() == size && intact(i, shard)).then(|| shard.to_vec()))
+            .collect();
+
+        let bad: Vec<bool> = shards.iter().map(Option::is_none).collect();
+        let lost = bad.iter().filter(|bad| **bad).count();
+        if lost > 0 && lost <= parity_shards {
+            ReedSolomon::new(data_shards, parity_shards)
+                .and_then(|code| code.reconstruct_data(&mut shards))
+                .expect("a stripe with as many intact shards as data shards can be rebuilt");
+        }
+
+        let offset = data.len();
+        for (i, shard) in shards.into_iter().take(data_shards).enumerate() {
+            let at = offset + i * size;
+            let shard = match shard {
+                Some(shard) => {
+                    if bad[i] {
+                        differences(read[i], &shard, at, &mut repaired);
+                    }
+                    shard
+                }
+                None => {
+                    push(&mut damaged, at..at + size);
+                    let mut shard = read[i].to_vec();
+                    shard.resize(size, 0);
+                    shard
+                }
+            };
+            data.extend(shard);
+        }
+        position = start + count * size + 4 * count;
+    }
+
+    if data.len() < header.length {
+        push(&mut damaged, data.len()..header.length);
+    }
+    data.truncate(header.length);
+    for ranges in [&mut repaired, &mut damaged].iter_mut() {
+        ranges.retain_mut(|range| {
+            range.end = range.end.min(header.length);
+            range.start < range.end
+        });
+    }
+
+    Some(Repair {
+        data,
+        repaired,
+        damaged,
+    })
+}
+
+/// Adds the ranges where the shard as read differs from the repaired one, bytes missing from
+/// the read one included.
+fn differences(read: &[u8], shard: &[u8], offset: usize, ranges: &mut Vec<Range<usize>>) {
+    for (i, byte) in shard.iter().enumerate() {
+        if read.get(i) != Some(byte) {
+            push(ranges, offset + i..offset + i + 1);
+        }
+    }
+}
+
+/// Adds the range, joining it to the last one when they touch.
+fn push(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn data(length: usize) -> Vec<u8> {
+        (0..length as u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 11) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        for length in [0, 1, 100, 64 * 4096, 64 * 4096 * 2 + 777] {
+            let data = data(length);
+            let protected = encode(&data, 10);
+
+            assert!(is_protected(&protected));
+            let repair = repair(&protected).unwrap();
+            assert_eq!(data, repair.data);
+            assert!(repair.repaired.is_empty() && repair.damaged.is_empty());
+        }
+        assert!(!is_protected(&data(100)));
+    }
+
+    #[test]
+    fn repairs_scattered_bytes() {
+        let data = data(100_000);
+        let mut protected = encode(&data, 10);
+        // Shards are 1563 bytes, a stripe of 64 has 7 parity shards.
+        let damage = [HEADER_SIZE + 4 * 71 + 10, HEADER_SIZE + 4 * 71 + 5000];
+        for at in damage.iter() {
+            protected[*at] ^= 0xff;
+        }
+
+        let repair = repair(&protected).unwrap();
+        assert_eq!(data, repair.data);
+        assert_eq!(vec![10..11, 5000..5001], repair.repaired);
+        assert!(repair.damaged.is_empty());
+    }
+
+    #[test]
+    fn repairs_a_burst_and_a_lost_header() {
+        let data = data(300_000);
+        let mut protected = encode(&data, 5);
+        // A bad sector, four whole shards in the first stripe, and the first header.
+        let start = HEADER_SIZE + 4 * (64 + 4) + 3 * 4096;
+        protected[start..start + 4 * 4096]
+            .iter_mut()
+            .for_each(|b| *b = 0);
+        protected[2] = b'X';
+
+        let repair = repair(&protected).unwrap();
+        assert_eq!(data, repair.data);
+        // Zeros in the data were not damaged, so the burst may be reported in pieces.
+        assert_eq!(3 * 4096, repair.repaired[0].start);
+        assert_eq!(7 * 4096, repair.repaired.last().unwrap().end);
+    }
+
+    #[test]
+    fn survives_a_cut_end() {
+        // Shards of 469 bytes, 64 data and 13 parity shards.
+        let data = data(30_000);
+        let protected = encode(&data, 20);
+        let cut = &protected[..protected.len() - HEADER_SIZE - 4 * 77 - 3 * 469];
+
+        let repair = repair(cut).unwrap();
+        assert_eq!(data, repair.data);
+        assert!(repair.repaired.is_empty() && repair.damaged.is_empty());
+    }
+
+    #[test]
+    fn length_beyond_the_source_is_reported_damaged() {
+        let header = Header {
+            percent: 10,
+            shard_size: MIN_SHARD_SIZE,
+            length: 1 << 50,
+        };
+
+        let alone = repair(&header.write()).unwrap();
+        assert!(alone.data.is_empty());
+        assert_eq!(vec![0..1 << 50], alone.damaged);
+
+        let source = [header.write(), data(10_000)].concat();
+        let followed = repair(&source).unwrap();
+        assert!(followed.data.len() < source.len());
+        assert_eq!(1 << 50, followed.damaged.last().unwrap().end);
+    }
+
+    #[test]
+    fn too_much_damage_is_reported() {
+        let data = data(100_000);
+        let mut protected = encode(&data, 1);
+        // One parity shard per stripe, and two damaged data shards.
+        let shards = HEADER_SIZE + 4 * 65;
+        protected[shards] ^= 1;
+        protected[shards + 1563] ^= 1;
+
+        let repair = repair(&protected).unwrap();
+        assert_eq!(vec![0..2 * 1563], repair.damaged);
+        assert_eq!(data[2 * 1563..], repair.data[2 * 1563..]);
+    }
+}
@@ -0,0 +1,260 @@
+//! An adaptive order-0 range coder. Both sides start with every byte equally likely and count
+//! the bytes as they go, so the probabilities follow the data and nothing but the coded bytes
+//! is stored. Each byte narrows a 32-bit range by its share of the counts, which costs a
+//! fraction of a bit for likely bytes.
+//!
+//! The coder is Subbotin's carryless one: when the top byte of the range cannot settle, the
+//! range is cut short instead of propagating a carry into bytes already written.
+
+use crate::error::{Error, Result};
+
+/// Bytes are written once the top byte of the range is settled.
+const TOP: u32 = 1 << 24;
+/// The smallest range, and the largest total count the model may reach.
+const BOTTOM: u32 = 1 << 16;
+/// How much a byte's count grows each time it occurs.
+const INCREMENT: u32 = 32;
+
+/// Counts of every byte value in a Fenwick tree, so cumulative counts and the byte a count
+/// falls on take a step per bit of the byte rather than a scan.
+struct Model {
+    tree: [u32; 257],
+    counts: [u32; 256],
+    total: u32,
+}
+
+impl Model {
+    fn new() -> Self {
+        let mut model = Self {
+            tree: [0; 257],
+            counts: [1; 256],
+            total: 0,
+        };
+        model.rebuild();
+        model
+    }
+
+    fn rebuild(&mut self) {
+        self.tree = [0; 257];
+        self.total = 0;
+        for s in 0..256 {
+            self.add(s, self.counts[s]);
+        }
+    }
+
+    fn add(&mut self, s: usize, count: u32) {
+        let mut i = s + 1;
+        while i <= 256 {
+            self.tree[i] += count;
+            i += i & i.wrapping_neg();
+        }
+        self.total += count;
+    }
+
+    /// The counts of the bytes below `s`.
+    fn cumulative(&self, s: usize) -> u32 {
+        let mut sum = 0;
+        let mut i = s;
+        while i > 0 {
+            sum += self.tree[i];
+            i -= i & i.wrapping_neg();
+        }
+        sum
+    }
+
+    /// The byte whose share of the counts holds `target`, and the counts below it.
+    fn find(&self, target: u32) -> (usize, u32) {
+        let mut position = 0;
+        let mut below = 0;
+        let mut step = 256;
+        while step > 0 {
+            if position + step <= 256 && below + self.tree[position + step] <= target {
+                position += step;
+                below += self.tree[position];
+            }
+            step >>= 1;
+        }
+        (position, below)
+    }
+
+    /// Counts an occurrence, halving all counts once the total outgrows the range.
+    fn update(&mut self, s: usize) {
+        self.counts[s] += INCREMENT;
+        self.add(s, INCREMENT);
+        if self.total > BOTTOM {
+            self.counts
+                .iter_mut()
+                .for_each(|count| *count = count.div_ceil(2));
+            self.rebuild();
+        }
+    }
+}
+
+pub fn encode(source: &[u8]) -> Vec<u8> {
+    let mut model = Model::new();
+    let mut encoder = Encoder {
+        low: 0,
+        range: u32::MAX,
+        output: Vec::with_capacity(source.len() / 2),
+    };
+
+    for t in source {
+        let s = *t as usize;
+        encoder.encode(model.cumulative(s), model.counts[s], model.total);
+        model.update(s);
+    }
+
+    encoder.finish()
+}
+
+/// Decodes `length` bytes. The length is not stored in the body, it is in the trailer.
+pub fn decode(body: &[u8], length: usize) -> Result<Vec<u8>> {
+    let mut model = Model::new();
+    let mut decoder = Decoder {
+        low: 0,
+        range: u32::MAX,
+        code: 0,
+        input: body,
+    };
+    for _ in 0..4 {
+        decoder.code = decoder.code << 8 | decoder.next()? as u32;
+    }
+
+    // A byte costs at least a fraction of a bit, so this only caps a corrupt length.
+    let mut output = Vec::with_capacity(length.min(body.len().saturating_mul(8)));
+    for _ in 0..length {
+        let target = decoder.target(model.total);
+        let (s, below) = model.find(target);
+        decoder.decode(below, model.counts[s])?;
+        model.update(s);
+        output.push(s as u8);
+    }
+
+    Ok(output)
+}
+
+struct Encoder {
+    low: u32,
+    range: u32,
+    output: Vec<u8>,
+}
+
+impl Encoder {
+    fn encode(&mut self, below: u32, count: u32, total: u32) {
+        self.range /= total;
+        self.low = self.low.wrapping_add(below * self.range);
+        self.range *= count;
+
+        while settled(self.low, &mut self.range) {
+            self.output.push((self.low >> 24) as u8);
+            self.low <<= 8;
+            self.range <<= 8;
+        }
+    }
+
+    /// Writes out the rest of `low`, which lies in the final range.
+    fn finish(mut self) -> Vec<u8> {
+        for _ in 0..4 {
+            self.output.push((self.low >> 24) as u8);
+            self.low <<= 8;
+        }
+        self.output
+    }
+}
+
+struct Decoder<'a> {
+    low: u32,
+    range: u32,
+    code: u32,
+    input: &'a [u8],
+}
+
+impl Decoder<'_> {
+    /// Where the code falls in the counts, which tells the model the byte.
+    fn target(&mut self, total: u32) -> u32 {
+        self.range /= total;
+        (self.code.wrapping_sub(self.low) / self.range).min(total - 1)
+    }
+
+    fn decode(&mut self, below: u32, count: u32) -> Result<()> {
+        self.low = self.low.wrapping_add(below * self.range);
+        self.range *= count;
+
+        while settled(self.low, &mut self.range) {
+            self.code = self.code << 8 | self.next()? as u32;
+            self.low <<= 8;
+            self.range <<= 8;
+        }
+        Ok(())
+    }
+
+    /// The encoder wrote every byte the decoder reads, so running out means truncation.
+    fn next(&mut self) -> Result<u8> {
+        let (byte, rest) = self.input.split_first().ok_or(Error::Truncated)?;
+        self.input = rest;
+        Ok(*byte)
+    }
+}
+
+/// Whether the top byte should be shifted out: it is the same across the range, or the range
+/// got too small, in which case it is cut to end where the top byte would change.
+fn settled(low: u32, range: &mut u32) -> bool {
+    if low ^ low.wrapping_add(*range) < TOP {
+        return true;
+    }
+    if *range < BOTTOM {
+        *range = low.wrapping_neg() & (BOTTOM - 1);
+        return true;
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let skewed: Vec<u8> = (0..200_000u32).map(|i| (i % 97 / 40) as u8).collect();
+        let all: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        let inputs: [&[u8]; 5] = [b"", b"a", b"abracadabra", &skewed, &all];
+
+        for source in inputs {
+            let encoded = encode(source);
+            assert_eq!(source, &decode(&encoded, source.len()).unwrap()[..]);
+        }
+    }
+
+    #[test]
+    fn adapts_to_the_data() {
+        // After a while the single byte costs a small fraction of a bit.
+        let source = vec![b'a'; 100_000];
+        assert!(encode(&source).len() < 1000);
+    }
+
+    #[test]
+    fn truncation_is_detected() {
+        let source = b"There and back again. A hobbits tale, by Bilbo Baggins";
+        let encoded = encode(source);
+
+        for len in 0..encoded.len() {
+            assert_eq!(Err(Error::Truncated), decode(&encoded[..len], source.len()));
+        }
+        assert_eq!(Err(Error::Truncated), decode(&encoded, 1 << 40));
+    }
+
+    #[test]
+    fn model_finds_cumulative_counts() {
+        let mut model = Model::new();
+        model.update(b'c' as usize);
+
+        let below = model.cumulative(b'c' as usize);
+        assert_eq!(b'c' as u32, below);
+        assert_eq!((b'c' as usize, below), model.find(below + INCREMENT));
+        assert_eq!(
+            (b'd' as usize, below + 1 + INCREMENT),
+            model.find(below + 1 + INCREMENT)
+        );
+        assert_eq!(256 + INCREMENT, model.total);
+    }
+}
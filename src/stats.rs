@@ -1,63 +1,538 @@
 use std::collections::HashMap;
+use std::str::FromStr;
 
-use super::huffman_tree::tree;
-use crate::format::{read_dictionary, read_sizes};
+use crate::bytes::BitFormat;
+use crate::compress::{decode, encode_huffman, encode_huffman_sampled, read_tree};
+use crate::error::Result;
+use crate::export::walk;
+use crate::format::{read_frame, read_sizes, Method};
+use crate::huffman_tree::tree::HuffmanTree;
 
 use prettytable::Table;
+use serde::Serialize;
 
-pub fn print(source: &[u8]) {
-    let (tokens, hits) = read_dictionary(source);
-    let (dictionary_size, size_when_compressed, _) = read_sizes(source);
+pub const FORMATS: &[&str] = &["table", "json", "csv"];
 
-    let hits_map: HashMap<&u8, &usize> = tokens.iter().zip(hits.iter()).collect();
-    let size = hits.iter().sum::<usize>();
-    let tree = tree::with_vecdeque(&tokens, &hits, size).unwrap();
-    let key_pairs = tree.stream_codes();
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub original_size: usize,
+    pub compressed_size: usize,
+    pub compression_percent: f64,
+    pub method: Method,
+    /// None for files that are not huffman coded. Their payload bits, information and symbols
+    /// are those huffman coding would give their data.
+    pub dictionary: Option<Dictionary>,
+    pub payload_bits: usize,
+    pub information: Information,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<Sampling>,
+    pub symbols: Vec<Symbol>,
+}
+
+/// What building the code from a sample costs against counting the whole source.
+#[derive(Debug, Serialize)]
+pub struct Sampling {
+    pub percent: u8,
+    pub full_compressed_size: usize,
+    /// Percentage points of compression lost to the sample.
+    pub ratio_loss: f64,
+}
+
+/// How close the code is to the order-0 entropy of the source. Rates are in bits per symbol.
+#[derive(Debug, Serialize)]
+pub struct Information {
+    pub entropy: f64,
+    pub average_code_length: f64,
+    pub redundancy: f64,
+    pub efficiency: f64,
+    pub kraft_sum: f64,
+    pub code_lengths: Vec<CodeLength>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodeLength {
+    pub bits: usize,
+    pub symbols: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Dictionary {
+    pub tokens: usize,
+    pub hits_size: usize,
+    pub total_bytes: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Symbol {
+    pub byte: u8,
+    pub character: String,
+    pub count: usize,
+    pub code: String,
+    pub bits: usize,
+    pub share: f64,
+}
+
+pub fn print(source: &[u8], format: Format) -> Result<()> {
+    output(&from(source)?, format);
+    Ok(())
+}
+
+/// Prints the stats of the source coded from a sample of `percent` of it.
+pub fn print_sampled(source: &[u8], percent: u8, format: Format) -> Result<()> {
+    output(&sampled(source, percent)?, format);
+    Ok(())
+}
+
+fn output(stats: &Stats, format: Format) {
+    match format {
+        Format::Table => print_table(stats),
+        Format::Json => println!("{}", to_json(stats)),
+        Format::Csv => print!("{}", to_csv(stats)),
+    }
+}
+
+/// The stats of the source coded from a sample, compared with coding it from all the counts.
+pub fn sampled(source: &[u8], percent: u8) -> Result<Stats> {
+    let mut stats = from(&encode_huffman_sampled(source, percent))?;
+    let full = from(&encode_huffman(source))?;
+
+    stats.sampling = Some(Sampling {
+        percent,
+        full_compressed_size: full.compressed_size,
+        ratio_loss: full.compression_percent - stats.compression_percent,
+    });
+    Ok(stats)
+}
+
+pub fn from(source: &[u8]) -> Result<Stats> {
+    let method = read_frame(source)?.method;
+    if let Method::Stored | Method::Ans | Method::Range | Method::Blocks = method {
+        return uncoded(source, method);
+    }
+
+    let tree = read_tree(source)?;
+    let (dictionary_size, size_when_compressed, _) = read_sizes(source)?;
+
+    let size = tree.root().freq();
 
     let compression_total = (size_when_compressed / 8) + dictionary_size;
-    let compression_percent = (1.0 - compression_total as f64 / size as f64) * 100.0;
+    let compression_percent = saving(compression_total, size);
+
+    let symbols = symbols(&tree, size_when_compressed);
+    let information = information(&symbols, size);
+    // Canonical files store a code length per byte value rather than tokens and counts.
+    let hits_size = match method {
+        Method::Huffman => dictionary_size - symbols.len(),
+        _ => 0,
+    };
+
+    Ok(Stats {
+        original_size: size,
+        compressed_size: compression_total,
+        compression_percent,
+        method,
+        dictionary: Some(Dictionary {
+            tokens: symbols.len(),
+            hits_size,
+            total_bytes: dictionary_size,
+        }),
+        payload_bits: size_when_compressed,
+        information,
+        sampling: None,
+        symbols,
+    })
+}
 
-    let mut rows = Vec::with_capacity(tokens.len());
-    for (t, codes) in key_pairs {
-        let binary_codes: String = codes
-            .into_iter()
-            .map(|c| if c { '0' } else { '1' })
-            .collect();
-        let hit = hits_map.get(&t).unwrap();
-        rows.push((t, **hit, binary_codes));
+/// Stats of a file that is not huffman coded, sized as the whole file.
+fn uncoded(source: &[u8], method: Method) -> Result<Stats> {
+    let data = decode(source)?;
+    let mut stats = if data.is_empty() {
+        Stats {
+            original_size: 0,
+            compressed_size: 0,
+            compression_percent: 0.0,
+            method,
+            dictionary: None,
+            payload_bits: 0,
+            information: information(&[], 0),
+            sampling: None,
+            symbols: Vec::new(),
+        }
+    } else {
+        from(&encode_huffman(&data))?
+    };
+
+    stats.method = method;
+    stats.dictionary = None;
+    stats.compressed_size = source.len();
+    stats.compression_percent = saving(source.len(), data.len());
+    Ok(stats)
+}
+
+/// The symbols of the tree with their counts and codes, most frequent first. `payload_bits` is
+/// the length of the payload the shares are of.
+pub fn symbols(tree: &HuffmanTree<u8>, payload_bits: usize) -> Vec<Symbol> {
+    let hits_map: HashMap<u8, usize> = walk(tree.root())
+        .iter()
+        .filter_map(|visit| visit.node.leaf().map(|t| (*t, visit.node.freq())))
+        .collect();
+    let key_pairs = tree.stream_codes();
+
+    let mut symbols = Vec::with_capacity(key_pairs.len());
+    for (t, code) in key_pairs {
+        let codes_len = code.len;
+        let binary_codes = code_string(&code.to_codes());
+        let hit = hits_map.get(&t).copied().unwrap_or_default();
+        symbols.push(Symbol {
+            byte: t,
+            character: character(t),
+            count: hit,
+            bits: binary_codes.len(),
+            code: binary_codes,
+            share: ratio(hit * codes_len, payload_bits),
+        });
     }
 
-    rows.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    symbols.sort_by(|a, b| b.count.cmp(&a.count).then(a.byte.cmp(&b.byte)));
+    symbols
+}
+
+fn information(symbols: &[Symbol], size: usize) -> Information {
+    let mut entropy = 0.0;
+    let mut average_code_length = 0.0;
+    let mut kraft_sum = 0.0;
+    let mut histogram = HashMap::new();
 
+    for symbol in symbols {
+        let p = ratio(symbol.count, size);
+        if p > 0.0 {
+            entropy -= p * p.log2();
+        }
+        average_code_length += p * symbol.bits as f64;
+        kraft_sum += 0.5f64.powi(symbol.bits as i32);
+        *histogram.entry(symbol.bits).or_insert(0) += 1;
+    }
+
+    let mut code_lengths: Vec<CodeLength> = histogram
+        .into_iter()
+        .map(|(bits, symbols)| CodeLength { bits, symbols })
+        .collect();
+    code_lengths.sort_by_key(|length| length.bits);
+
+    Information {
+        entropy,
+        average_code_length,
+        redundancy: average_code_length - entropy,
+        efficiency: if average_code_length > 0.0 {
+            entropy / average_code_length
+        } else {
+            1.0
+        },
+        kraft_sum,
+        code_lengths,
+    }
+}
+
+/// The percentage of the original size saved, zero for empty sources.
+fn saving(compressed: usize, original: usize) -> f64 {
+    if original == 0 {
+        return 0.0;
+    }
+
+    (1.0 - ratio(compressed, original)) * 100.0
+}
+
+/// Zero instead of NaN for empty sources.
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        return 0.0;
+    }
+
+    numerator as f64 / denominator as f64
+}
+
+/// The digits of a code as it is written, see `BitFormat::HUFF`.
+pub fn code_string(codes: &[bool]) -> String {
+    let polarity = BitFormat::HUFF.polarity;
+    codes.iter().map(|left| polarity.digit(*left)).collect()
+}
+
+/// Printable ASCII as-is, everything else escaped, e.g. `\n` or `\xff`.
+pub fn character(t: u8) -> String {
+    std::ascii::escape_default(t).map(char::from).collect()
+}
+
+fn to_json(stats: &Stats) -> String {
+    // Plain structs of numbers and strings always serialise; NaN becomes null.
+    serde_json::to_string_pretty(stats).unwrap_or_default()
+}
+
+fn to_csv(stats: &Stats) -> String {
+    let mut csv = String::from("metric,value\n");
+    csv += &format!("original_size,{}\n", stats.original_size);
+    csv += &format!("compressed_size,{}\n", stats.compressed_size);
+    csv += &format!("compression_percent,{:.2}\n", stats.compression_percent);
+    csv += &format!("method,{}\n", stats.method.name());
+    // Files without a dictionary leave its values empty.
+    let field = |value: fn(&Dictionary) -> usize| {
+        stats
+            .dictionary
+            .as_ref()
+            .map(value)
+            .map(|value| value.to_string())
+            .unwrap_or_default()
+    };
+    csv += &format!("dictionary_tokens,{}\n", field(|d| d.tokens));
+    csv += &format!("dictionary_hits_size,{}\n", field(|d| d.hits_size));
+    csv += &format!("dictionary_total_bytes,{}\n", field(|d| d.total_bytes));
+    csv += &format!("payload_bits,{}\n", stats.payload_bits);
+
+    let information = &stats.information;
+    csv += &format!("entropy,{:.6}\n", information.entropy);
+    csv += &format!(
+        "average_code_length,{:.6}\n",
+        information.average_code_length
+    );
+    csv += &format!("redundancy,{:.6}\n", information.redundancy);
+    csv += &format!("efficiency,{:.6}\n", information.efficiency);
+    csv += &format!("kraft_sum,{:.6}\n", information.kraft_sum);
+    for length in &information.code_lengths {
+        csv += &format!("code_length_{}_bits,{}\n", length.bits, length.symbols);
+    }
+    if let Some(sampling) = &stats.sampling {
+        csv += &format!("sampling_percent,{}\n", sampling.percent);
+        csv += &format!(
+            "sampling_full_compressed_size,{}\n",
+            sampling.full_compressed_size
+        );
+        csv += &format!("sampling_ratio_loss,{:.2}\n", sampling.ratio_loss);
+    }
+
+    csv += "\nbyte,character,count,code,bits,share\n";
+    for symbol in &stats.symbols {
+        csv += &format!(
+            "{},\"{}\",{},{},{},{:.6}\n",
+            symbol.byte,
+            symbol.character.replace('"', "\"\""),
+            symbol.count,
+            symbol.code,
+            symbol.bits,
+            symbol.share
+        );
+    }
+
+    csv
+}
+
+fn print_table(stats: &Stats) {
     println!(
         "Compression ratio: {}/{}; {:.2}%",
-        compression_total, size, compression_percent
+        stats.compressed_size, stats.original_size, stats.compression_percent
     );
-    println!("Dictionary stats:");
-    println!("Tokens:\t{}", tokens.len());
-    println!("Hits size:\t{}", dictionary_size - tokens.len());
-    println!("Total bytes:\t{}", dictionary_size);
+    if let Some(sampling) = &stats.sampling {
+        println!(
+            "Sampled {}%: {:.2} points below the full count ({} bytes)",
+            sampling.percent, sampling.ratio_loss, sampling.full_compressed_size
+        );
+    }
+    println!("Method:\t{}", stats.method.name());
+    match &stats.dictionary {
+        Some(dictionary) => {
+            println!("Dictionary stats:");
+            println!("Tokens:\t{}", dictionary.tokens);
+            println!("Hits size:\t{}", dictionary.hits_size);
+            println!("Total bytes:\t{}", dictionary.total_bytes);
+        }
+        None => println!("Not huffman coded; the stats below are of huffman coding its data."),
+    }
 
-    print_table(rows);
-}
+    let information = &stats.information;
+    println!("Information:");
+    println!("Entropy:\t{:.4} bits/symbol", information.entropy);
+    println!(
+        "Average code length:\t{:.4} bits/symbol",
+        information.average_code_length
+    );
+    println!("Redundancy:\t{:.4} bits/symbol", information.redundancy);
+    println!("Efficiency:\t{:.2}%", information.efficiency * 100.0);
+    println!("Kraft sum:\t{}", information.kraft_sum);
+    println!("Code lengths:");
+    for length in &information.code_lengths {
+        println!("{} bits:\t{} symbols", length.bits, length.symbols);
+    }
 
-fn print_table(row: Vec<(u8, usize, String)>) {
     let mut table = Table::new();
-    table.add_row(row!["#", "Character", "Count", "Code", "Bits"]);
+    table.add_row(row!["#", "Character", "Count", "Code", "Bits", "Share"]);
 
-    for (i, (t, x, c)) in row.into_iter().enumerate() {
+    for (i, symbol) in stats.symbols.iter().enumerate() {
         table.add_row(row![
             i,
-            format!(
-                "{:?}",
-                String::from_utf8(vec![t]).unwrap_or_else(|t| format!("{}", t))
-            ),
-            x,
-            c,
-            c.len(),
+            format!("\"{}\"", symbol.character),
+            symbol.count,
+            symbol.code,
+            symbol.bits,
+            format!("{:.2}%", symbol.share * 100.0),
         ]);
     }
 
     println!("Dictionary contents:");
     table.printstd();
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compress::encode_huffman as encode;
+
+    #[test]
+    fn from_encoded() {
+        let stats = from(&encode(b"aaaaaaaaabbbbbbbbb\n")).unwrap();
+
+        assert_eq!(19, stats.original_size);
+        assert_eq!(29, stats.payload_bits);
+        assert_eq!(3, stats.dictionary.unwrap().tokens);
+        assert_eq!(
+            vec![(b'a', 9, 1), (b'b', 9, 2), (b'\n', 1, 2)],
+            stats
+                .symbols
+                .iter()
+                .map(|s| (s.byte, s.count, s.bits))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn csv_quotes_characters() {
+        let csv = to_csv(&from(&encode(b"\"\"a")).unwrap());
+
+        assert!(csv.starts_with("metric,value\noriginal_size,3\n"));
+        assert!(csv.contains("\n34,\"\\\"\"\",2,"));
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let json = to_json(&from(&encode(b"geeksforgeeks")).unwrap());
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(13, value["original_size"]);
+        assert_eq!(7, value["symbols"].as_array().unwrap().len());
+    }
+
+    #[test]
+    fn stored_files() {
+        let stored = crate::compress::encode(b"ab");
+        assert_eq!(Method::Stored, read_frame(&stored).unwrap().method);
+
+        let stats = from(&stored).unwrap();
+        assert_eq!(Method::Stored, stats.method);
+        assert!(stats.dictionary.is_none());
+        assert_eq!(
+            (2, stored.len()),
+            (stats.original_size, stats.compressed_size)
+        );
+        assert_eq!(2, stats.symbols.len());
+
+        let stats = from(&crate::compress::encode(b"")).unwrap();
+        assert_eq!((0, 0.0), (stats.original_size, stats.compression_percent));
+        assert!(stats.symbols.is_empty());
+    }
+
+    #[test]
+    fn ans_files() {
+        let source = b"geeksforgeeks".repeat(20);
+        let ans = crate::compress::encode_ans(&source);
+        assert_eq!(Method::Ans, read_frame(&ans).unwrap().method);
+
+        let stats = from(&ans).unwrap();
+        assert_eq!(Method::Ans, stats.method);
+        assert!(stats.dictionary.is_none());
+        assert_eq!(
+            (source.len(), ans.len()),
+            (stats.original_size, stats.compressed_size)
+        );
+    }
+
+    #[test]
+    fn range_files() {
+        let source = b"geeksforgeeks".repeat(20);
+        let range = crate::compress::encode_range(&source);
+        assert_eq!(Method::Range, read_frame(&range).unwrap().method);
+
+        let stats = from(&range).unwrap();
+        assert_eq!(Method::Range, stats.method);
+        assert_eq!(source.len(), stats.original_size);
+        assert_eq!(
+            13,
+            stats.symbols.iter().map(|s| s.count).sum::<usize>() / 20
+        );
+    }
+
+    #[test]
+    fn blocks_files() {
+        let source = b"geeksforgeeks".repeat(20);
+        let blocks = crate::blocks::encode(&source, 64, crate::compress::encode);
+        assert_eq!(Method::Blocks, read_frame(&blocks).unwrap().method);
+
+        let stats = from(&blocks).unwrap();
+        assert_eq!(Method::Blocks, stats.method);
+        assert_eq!(
+            (source.len(), blocks.len()),
+            (stats.original_size, stats.compressed_size)
+        );
+    }
+
+    #[test]
+    fn information_of_uniform_source() {
+        let information = from(&encode(b"abcdabcd")).unwrap().information;
+
+        assert_eq!(2.0, information.entropy);
+        assert_eq!(2.0, information.average_code_length);
+        assert_eq!(1.0, information.efficiency);
+        assert_eq!(1.0, information.kraft_sum);
+        assert_eq!(1, information.code_lengths.len());
+        assert_eq!(4, information.code_lengths[0].symbols);
+    }
+
+    #[test]
+    fn sampling_loss() {
+        let mut source = vec![b'a'; 1 << 20];
+        source.extend((0..1u32 << 16).map(|i| (i % 200) as u8));
+        let stats = sampled(&source, 1).unwrap();
+        let sampling = stats.sampling.as_ref().unwrap();
+
+        assert_eq!(source.len(), stats.original_size);
+        assert!(sampling.full_compressed_size < stats.compressed_size);
+        assert!(sampling.ratio_loss > 0.0);
+        assert!(to_csv(&stats).contains("\nsampling_percent,1\n"));
+        assert!(to_json(&from(&encode(b"ab")).unwrap())
+            .find("sampling")
+            .is_none());
+    }
+
+    #[test]
+    fn escaped_characters() {
+        assert_eq!("a", character(b'a'));
+        assert_eq!("\\n", character(b'\n'));
+        assert_eq!("\\xff", character(0xff));
+    }
+}
@@ -0,0 +1,329 @@
+//! Encoding seekable inputs in two passes, so memory use does not grow with the input: the
+//! first pass counts bytes, the second reads them again and writes the payload as it goes.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::bytes::{usize_to_padded_varint, BitFormat, Codes};
+use crate::compress::plan;
+use crate::format::{write_head, write_trailer, Method};
+use crate::histogram::{self, Histogram, SAMPLE_CHUNK};
+use crate::huffman_tree::strategy::Strategy;
+
+/// How much of the input is read at a time.
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// Writes the same frame as `compress::encode_with` for the rest of the input. Fails with
+/// `InvalidData` when the input changes between the passes.
+pub fn encode<R, W>(input: &mut R, output: &mut W, strategy: Strategy) -> io::Result<()>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    let start = input.stream_position()?;
+    let (histogram, checksum, length) = scan(input)?;
+    let plan = plan(&histogram, strategy);
+    input.seek(SeekFrom::Start(start))?;
+
+    let (rescanned, relength) = if plan.shrinks(length) {
+        output.write_all(&write_head(Method::Canonical))?;
+        output.write_all(&plan.header())?;
+        let (checksum, length, _) = write_payload(input, output, &plan.codes)?;
+        (checksum, length)
+    } else {
+        output.write_all(&write_head(Method::Stored))?;
+        copy(input, output)?
+    };
+    if (rescanned, relength) != (checksum, length) {
+        return Err(changed());
+    }
+
+    output.write_all(&write_trailer(checksum, length))
+}
+
+/// Like `encode`, building the codes from a sample of `percent` of the input, so the input is
+/// read once rather than twice. The payload size is only known at the end, so it is written
+/// padded and overwritten in place, which needs a seekable output. Whether to store the input
+/// is decided on the estimated size.
+pub fn encode_sampled<R, W>(
+    input: &mut R,
+    output: &mut W,
+    strategy: Strategy,
+    percent: u8,
+) -> io::Result<()>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    let start = input.stream_position()?;
+    let length = (input.seek(SeekFrom::End(0))? - start) as usize;
+
+    let mut histogram = [0; 256];
+    let mut buffer = Vec::with_capacity(SAMPLE_CHUNK);
+    for offset in histogram::sample_starts(length, percent) {
+        input.seek(SeekFrom::Start(start + offset as u64))?;
+        buffer.clear();
+        input
+            .by_ref()
+            .take(SAMPLE_CHUNK as u64)
+            .read_to_end(&mut buffer)?;
+        histogram::add(&mut histogram, &histogram::count_serial(&buffer));
+    }
+    histogram::smooth(&mut histogram);
+
+    let mut plan = plan(&histogram, strategy);
+    // Scale the payload size of the sample up to the whole input.
+    let sampled = histogram.iter().sum::<u64>();
+    plan.payload_bits = (plan.payload_bits as u128 * length as u128 / sampled as u128) as usize;
+    input.seek(SeekFrom::Start(start))?;
+
+    let (checksum, relength) = if plan.shrinks(length) {
+        output.write_all(&write_head(Method::Canonical))?;
+        let payload_size = output.stream_position()?;
+        output.write_all(&usize_to_padded_varint(0))?;
+        output.write_all(&plan.code_lengths)?;
+        let (checksum, length, bits) = write_payload(input, output, &plan.codes)?;
+
+        let end = output.stream_position()?;
+        output.seek(SeekFrom::Start(payload_size))?;
+        output.write_all(&usize_to_padded_varint(bits))?;
+        output.seek(SeekFrom::Start(end))?;
+        (checksum, length)
+    } else {
+        output.write_all(&write_head(Method::Stored))?;
+        copy(input, output)?
+    };
+    if relength != length {
+        return Err(changed());
+    }
+
+    output.write_all(&write_trailer(checksum, length))
+}
+
+fn changed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "input changed while it was compressed",
+    )
+}
+
+/// The first pass: counts, checksum and length of the input.
+fn scan<R: Read>(input: &mut R) -> io::Result<(Histogram, u32, usize)> {
+    let mut histogram = [0; 256];
+    let (checksum, length) = chunks(input, |chunk| {
+        histogram::add(&mut histogram, &histogram::count_serial(chunk));
+        Ok(())
+    })?;
+
+    Ok((histogram, checksum, length))
+}
+
+/// Stores the input as-is, returning its checksum and length.
+fn copy<R: Read, W: Write>(input: &mut R, output: &mut W) -> io::Result<(u32, usize)> {
+    chunks(input, |chunk| output.write_all(chunk))
+}
+
+/// Writes the code of every byte, packed like `BitFormat::HUFF`, returning the checksum and
+/// length of what was read and the number of bits written.
+fn write_payload<R: Read, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    codes: &[(u8, Codes)],
+) -> io::Result<(u32, usize, usize)> {
+    // Each code as written, its first bit lowest, the order `BitFormat::HUFF` packs bits in.
+    let polarity = BitFormat::HUFF.polarity;
+    let mut table = [(0u128, 0usize); 256];
+    for (t, code) in codes {
+        let bits = code.iter().enumerate().fold(0, |bits, (i, left)| {
+            bits | (polarity.bit(*left) as u128) << i
+        });
+        table[*t as usize] = (bits, code.len());
+    }
+
+    let mut writer = BitWriter::new(output);
+    let mut written = 0;
+    let (checksum, length) = chunks(input, |chunk| {
+        for byte in chunk {
+            let (bits, len) = table[*byte as usize];
+            writer.write(bits, len)?;
+            written += len;
+        }
+        Ok(())
+    })?;
+    writer.finish()?;
+
+    Ok((checksum, length, written))
+}
+
+/// Calls `f` with every chunk of the input, returning the checksum and length of all of them.
+fn chunks<R, F>(input: &mut R, mut f: F) -> io::Result<(u32, usize)>
+where
+    R: Read,
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut hasher = crc32fast::Hasher::new();
+    let mut length = 0;
+
+    loop {
+        let read = match input.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        let chunk = &buffer[..read];
+        hasher.update(chunk);
+        length += read;
+        f(chunk)?;
+    }
+
+    Ok((hasher.finalize(), length))
+}
+
+/// Packs bits into bytes, lowest bit first, and writes them out a buffer at a time.
+struct BitWriter<'a, W: Write> {
+    output: &'a mut W,
+    buffer: Vec<u8>,
+    pending: u64,
+    count: usize,
+}
+
+impl<'a, W: Write> BitWriter<'a, W> {
+    fn new(output: &'a mut W) -> Self {
+        Self {
+            output,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            pending: 0,
+            count: 0,
+        }
+    }
+
+    /// Writes the lowest `len` bits, lowest first.
+    fn write(&mut self, mut bits: u128, mut len: usize) -> io::Result<()> {
+        while len > 0 {
+            let take = len.min(32);
+            self.pending |= ((bits as u64) & ((1 << take) - 1)) << self.count;
+            self.count += take;
+            bits >>= take;
+            len -= take;
+
+            while self.count >= 8 {
+                self.buffer.push(self.pending as u8);
+                self.pending >>= 8;
+                self.count -= 8;
+            }
+        }
+
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.output.write_all(&self.buffer)?;
+            self.buffer.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Pads the last byte with zeros and writes what is left.
+    fn finish(mut self) -> io::Result<()> {
+        if self.count > 0 {
+            self.buffer.push(self.pending as u8);
+        }
+
+        self.output.write_all(&self.buffer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bytes::{bytes_from, MAX_VARINT};
+    use crate::compress::{decode, encode_sampled as encode_sampled_in_memory, encode_with};
+    use crate::format::read_frame;
+    use std::io::Cursor;
+
+    fn streamed(source: &[u8], strategy: Strategy) -> Vec<u8> {
+        let mut output = Vec::new();
+        encode(&mut Cursor::new(source), &mut output, strategy).unwrap();
+        output
+    }
+
+    #[test]
+    fn matches_in_memory_encoding() {
+        let skewed: Vec<u8> = (0..200_000u32).map(|i| (i % 7 * i % 13) as u8).collect();
+        let uniform: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        let inputs: [&[u8]; 5] = [b"", b"a", b"abracadabra", &skewed, &uniform];
+
+        for source in inputs {
+            for strategy in [Strategy::TwoQueue, Strategy::Heap, Strategy::Moffat] {
+                let output = streamed(source, strategy);
+                assert_eq!(encode_with(source, strategy), output);
+                assert_eq!(source, &decode(&output).unwrap()[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn sampled_round_trip() {
+        // Bytes past the first few chunks are missing from a small sample.
+        let mut source: Vec<u8> = (0..12 * SAMPLE_CHUNK).map(|i| (i % 5) as u8).collect();
+        source.extend_from_slice(b"rare bytes at the end");
+
+        for percent in [5, 100] {
+            let mut output = Cursor::new(Vec::new());
+            encode_sampled(
+                &mut Cursor::new(&source),
+                &mut output,
+                Strategy::default(),
+                percent,
+            )
+            .unwrap();
+            let output = output.into_inner();
+
+            assert_eq!(Method::Canonical, read_frame(&output).unwrap().method);
+            assert_eq!(source, decode(&output).unwrap());
+            let in_memory = encode_sampled_in_memory(&source, Strategy::default(), percent);
+            assert!(output.len() <= in_memory.len() + MAX_VARINT);
+        }
+    }
+
+    #[test]
+    fn sampled_stores_random_data() {
+        let source: Vec<u8> = (0..3 * SAMPLE_CHUNK as u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+
+        let mut output = Cursor::new(Vec::new());
+        encode_sampled(
+            &mut Cursor::new(&source),
+            &mut output,
+            Strategy::default(),
+            50,
+        )
+        .unwrap();
+        let output = output.into_inner();
+
+        assert_eq!(Method::Stored, read_frame(&output).unwrap().method);
+        assert_eq!(source, decode(&output).unwrap());
+    }
+
+    #[test]
+    fn bit_writer_packs_like_bytes_from() {
+        let codes: Vec<Codes> = vec![
+            vec![true],
+            (0..100).map(|i| i % 3 == 0).collect(),
+            vec![false, true, true],
+        ];
+
+        let mut output = Vec::new();
+        let mut writer = BitWriter::new(&mut output);
+        for code in &codes {
+            let bits = code
+                .iter()
+                .enumerate()
+                .fold(0, |bits, (i, bit)| bits | (*bit as u128) << i);
+            writer.write(bits, code.len()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        assert_eq!(bytes_from(codes.concat()), output);
+    }
+}